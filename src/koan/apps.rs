@@ -2,6 +2,11 @@ use super::{KoanWM, config::AUTO_START, spawn};
 
 impl KoanWM {
     pub fn exec_autostart(&mut self) {
+        // set by the integration tests, they run against a bare Xvfb
+        if std::env::var_os("KOAN_NO_AUTOSTART").is_some() {
+            return;
+        }
+
         for app in AUTO_START {
             spawn(app);
         }
@@ -0,0 +1,396 @@
+use std::{
+    cell::RefCell,
+    collections::{HashMap, VecDeque},
+    rc::Rc,
+    time::Duration,
+};
+
+use super::{Atom, Backend, Event, Output, Waker, WindowAttributes};
+use crate::koan::{config::KeyBinding, layouts::Rect, KoanWM, Window};
+
+const ROOT: Window = 1;
+
+// monitors shared by the tests, a 1080p one and a smaller one at its right
+pub const SCREEN: Rect = Rect {
+    x: 0,
+    y: 0,
+    w: 1920,
+    h: 1080,
+};
+pub const SCREENS: [Rect; 2] = [
+    SCREEN,
+    Rect {
+        x: 1920,
+        y: 0,
+        w: 1280,
+        h: 1024,
+    },
+];
+
+#[derive(Default)]
+pub struct FakeWindow {
+    pub attrs: WindowAttributes,
+    pub mapped: bool,
+    pub border_width: u32,
+    pub border_color: String,
+    pub transient_for: Option<Window>,
+    pub title: Option<String>,
+    pub class: Vec<String>,
+    pub properties: HashMap<Atom, Vec<Atom>>,
+    pub strings: HashMap<Atom, Vec<String>>,
+    pub urgent: bool,
+    pub input_selected: bool,
+    pub embedder: Option<Window>,  // tray the icon is docked in
+    pub texts: Vec<(i32, String)>, // drawn since the last clear, with their x
+}
+
+#[derive(Default)]
+pub struct FakeState {
+    pub windows: HashMap<Window, FakeWindow>,
+    pub monitors: Vec<Output>,
+    pub events: VecDeque<Event>,
+    pub atoms: Vec<String>,
+    pub focus: Option<Window>,
+    pub pointer: (i32, i32),
+    pub protocols_sent: Vec<(Window, Atom)>,
+    pub tray: Option<Window>,
+    last_window: Window,
+}
+
+// in-memory display server, clones share the same state so a test can keep
+// one handle while KoanWM owns the other
+#[derive(Clone, Default)]
+pub struct Fake {
+    pub state: Rc<RefCell<FakeState>>,
+}
+
+impl Fake {
+    pub fn new(monitors: &[Rect]) -> Self {
+        let fake = Self::default();
+        fake.state.borrow_mut().last_window = ROOT;
+        fake.state
+            .borrow_mut()
+            .windows
+            .insert(ROOT, FakeWindow::default());
+        let names: Vec<String> = (0..monitors.len()).map(|i| format!("OUT-{}", i)).collect();
+        let outputs: Vec<(&str, Rect)> = names
+            .iter()
+            .map(String::as_str)
+            .zip(monitors.iter().copied())
+            .collect();
+        fake.set_outputs(&outputs);
+        fake
+    }
+
+    // what the next monitor query answers, like plugging or unplugging outputs
+    pub fn set_outputs(&self, outputs: &[(&str, Rect)]) {
+        self.state.borrow_mut().monitors = outputs
+            .iter()
+            .enumerate()
+            .map(|(i, &(name, rect))| Output {
+                name: name.to_string(),
+                rect,
+                primary: i == 0,
+            })
+            .collect();
+    }
+
+    // a koan instance driven by this fake, with its monitors already queried.
+    // It has no modules or key bindings, tests set the ones they use so
+    // editing config.rs does not break them
+    pub fn koan(&self) -> KoanWM {
+        let mut wm = KoanWM::with_backend(Box::new(self.clone()));
+        wm.modules = &[];
+        wm.key_bindings = &[];
+        wm.update_monitors();
+        wm
+    }
+
+    // what an application does before asking to be mapped
+    pub fn create_window(&self, width: i32, height: i32) -> Window {
+        self.add_window(WindowAttributes {
+            width,
+            height,
+            ..Default::default()
+        })
+    }
+
+    fn add_window(&self, attrs: WindowAttributes) -> Window {
+        let mut state = self.state.borrow_mut();
+        state.last_window += 1;
+        let window = state.last_window;
+        state.windows.insert(
+            window,
+            FakeWindow {
+                attrs,
+                ..Default::default()
+            },
+        );
+        window
+    }
+
+    // the property change is only reported if koan selected the client input
+    pub fn set_title(&self, window: Window, title: &str) {
+        let atom = self.intern_atom("_NET_WM_NAME");
+        let selected = self.with_window(window, |w| {
+            w.title = Some(title.to_string());
+            w.input_selected
+        });
+        if selected {
+            self.push(Event::PropertyNotify { window, atom });
+        }
+    }
+
+    pub fn push(&self, event: Event) {
+        self.state.borrow_mut().events.push_back(event);
+    }
+
+    pub fn with_window<T>(&self, window: Window, f: impl FnOnce(&mut FakeWindow) -> T) -> T {
+        f(self.state.borrow_mut().windows.get_mut(&window).unwrap())
+    }
+
+    pub fn geometry(&self, window: Window) -> Rect {
+        self.with_window(window, |w| Rect {
+            x: w.attrs.x,
+            y: w.attrs.y,
+            w: w.attrs.width as u32,
+            h: w.attrs.height as u32,
+        })
+    }
+
+    pub fn focus(&self) -> Option<Window> {
+        self.state.borrow().focus
+    }
+
+    fn update(&self, window: Window, f: impl FnOnce(&mut FakeWindow)) {
+        if let Some(w) = self.state.borrow_mut().windows.get_mut(&window) {
+            f(w);
+        }
+    }
+}
+
+impl Backend for Fake {
+    fn root(&self) -> Window {
+        ROOT
+    }
+
+    fn intern_atom(&self, name: &str) -> Atom {
+        let mut state = self.state.borrow_mut();
+        let idx = match state.atoms.iter().position(|a| a == name) {
+            Some(idx) => idx,
+            None => {
+                state.atoms.push(name.to_string());
+                state.atoms.len() - 1
+            }
+        };
+        idx as Atom + 1
+    }
+
+    fn flush(&self) {}
+
+    fn next_event(&self) -> Option<Event> {
+        self.state.borrow_mut().events.pop_front()
+    }
+
+    fn select_root_input(&self) {}
+
+    fn select_client_input(&self, window: Window) {
+        self.update(window, |w| w.input_selected = true);
+    }
+
+    fn grab_keys(&self, _bindings: &[KeyBinding]) {}
+
+    fn spawn_timer(&self, _interval: Duration) {}
+
+    // tests push Event::ModuleOutput themselves
+    fn waker(&self) -> Waker {
+        Box::new(|| true)
+    }
+
+    fn monitors(&self) -> Vec<Output> {
+        self.state.borrow().monitors.clone()
+    }
+
+    fn warp_pointer(&self, x: i32, y: i32) {
+        self.state.borrow_mut().pointer = (x, y);
+    }
+
+    fn window_attributes(&self, window: Window) -> Option<WindowAttributes> {
+        self.state.borrow().windows.get(&window).map(|w| w.attrs)
+    }
+
+    fn transient_for(&self, window: Window) -> Option<Window> {
+        self.state.borrow().windows.get(&window)?.transient_for
+    }
+
+    fn window_title(&self, window: Window) -> Option<String> {
+        self.state.borrow().windows.get(&window)?.title.clone()
+    }
+
+    fn window_class(&self, window: Window) -> Vec<String> {
+        self.state
+            .borrow()
+            .windows
+            .get(&window)
+            .map(|w| w.class.clone())
+            .unwrap_or_default()
+    }
+
+    fn get_atoms(&self, window: Window, property: Atom) -> Vec<Atom> {
+        let state = self.state.borrow();
+        state
+            .windows
+            .get(&window)
+            .and_then(|w| w.properties.get(&property).cloned())
+            .unwrap_or_default()
+    }
+
+    fn append_atom(&self, window: Window, property: Atom, value: Atom) {
+        self.update(window, |w| {
+            w.properties.entry(property).or_default().push(value)
+        });
+    }
+
+    fn get_strings(&self, window: Window, property: Atom) -> Vec<String> {
+        let state = self.state.borrow();
+        state
+            .windows
+            .get(&window)
+            .and_then(|w| w.strings.get(&property).cloned())
+            .unwrap_or_default()
+    }
+
+    fn set_strings(&self, window: Window, property: Atom, values: &[String]) {
+        self.update(window, |w| {
+            w.strings.insert(property, values.to_vec());
+        });
+    }
+
+    fn set_cardinals(&self, window: Window, property: Atom, values: &[u32]) {
+        self.update(window, |w| {
+            w.properties.insert(property, values.to_vec());
+        });
+    }
+
+    fn is_urgent(&self, window: Window) -> bool {
+        self.state
+            .borrow()
+            .windows
+            .get(&window)
+            .is_some_and(|w| w.urgent)
+    }
+
+    fn send_protocol(&self, window: Window, protocol: Atom) {
+        self.state
+            .borrow_mut()
+            .protocols_sent
+            .push((window, protocol));
+    }
+
+    fn acquire_tray(&self, tray: Window) -> bool {
+        let mut state = self.state.borrow_mut();
+        if state.tray.is_some() {
+            return false;
+        }
+        state.tray = Some(tray);
+        true
+    }
+
+    fn dock_icon(&self, tray: Window, icon: Window, _background: &str) -> bool {
+        let mut state = self.state.borrow_mut();
+        let Some(w) = state.windows.get_mut(&icon) else {
+            return false;
+        };
+        w.embedder = Some(tray);
+        w.mapped
+    }
+
+    fn undock_icon(&self, icon: Window) {
+        self.update(icon, |w| w.embedder = None);
+    }
+
+    // _XEMBED_INFO is kept with the atom lists, as version and flags
+    fn xembed_mapped(&self, icon: Window) -> bool {
+        let info = self.intern_atom("_XEMBED_INFO");
+        self.get_atoms(icon, info)
+            .get(1)
+            .is_none_or(|flags| flags & 1 != 0)
+    }
+
+    fn map(&self, window: Window) {
+        self.update(window, |w| w.mapped = true);
+    }
+
+    fn map_raised(&self, window: Window) {
+        self.map(window);
+    }
+
+    fn unmap(&self, window: Window) {
+        self.update(window, |w| w.mapped = false);
+    }
+
+    fn raise(&self, _window: Window) {}
+
+    fn move_window(&self, window: Window, x: i32, y: i32) {
+        self.update(window, |w| {
+            w.attrs.x = x;
+            w.attrs.y = y;
+        });
+    }
+
+    fn resize_window(&self, window: Window, width: u32, height: u32) {
+        self.update(window, |w| {
+            w.attrs.width = width as i32;
+            w.attrs.height = height as i32;
+        });
+    }
+
+    fn move_resize(&self, window: Window, rect: Rect) {
+        self.move_window(window, rect.x, rect.y);
+        self.resize_window(window, rect.w, rect.h);
+    }
+
+    fn set_border_width(&self, window: Window, width: u32) {
+        self.update(window, |w| w.border_width = width);
+    }
+
+    fn set_border_color(&self, window: Window, color: &str) {
+        self.update(window, |w| w.border_color = color.to_string());
+    }
+
+    fn set_input_focus(&self, window: Window) {
+        self.state.borrow_mut().focus = Some(window);
+    }
+
+    fn create_bar(&self, rect: Rect, _background: &str, _clickable: bool) -> Window {
+        self.add_window(WindowAttributes {
+            x: rect.x,
+            y: rect.y,
+            width: rect.w as i32,
+            height: rect.h as i32,
+            override_redirect: true,
+        })
+    }
+
+    fn destroy_window(&self, window: Window) {
+        self.state.borrow_mut().windows.remove(&window);
+    }
+
+    fn clear(&self, window: Window) {
+        self.update(window, |w| w.texts.clear());
+    }
+
+    fn fill_rect(&self, _window: Window, _rect: Rect, _color: &str) {}
+
+    fn draw_text(&self, window: Window, x: i32, _y: i32, text: &str, _color: &str) {
+        self.update(window, |w| w.texts.push((x, text.to_string())));
+    }
+
+    fn text_width(&self, text: &str) -> i32 {
+        text.chars().count() as i32 * 8
+    }
+
+    fn text_baseline(&self, height: u32) -> i32 {
+        height as i32 - 4
+    }
+}
@@ -0,0 +1,131 @@
+pub mod xorg;
+
+mod xft;
+
+#[cfg(test)]
+pub mod fake;
+
+use std::time::Duration;
+use x11rb::protocol::xproto::{ButtonIndex, ModMask};
+
+use super::{config::KeyBinding, layouts::Rect, Window};
+
+pub type Atom = u32;
+
+// wakes the event loop from another thread with Event::ModuleOutput, false
+// once the display is gone
+pub type Waker = Box<dyn Fn() -> bool + Send>;
+
+pub enum Event {
+    MapRequest(Window),
+    UnmapNotify(Window),
+    DestroyNotify(Window),
+    EnterNotify {
+        window: Window,
+        x_root: i32,
+        y_root: i32,
+    },
+    KeyPress {
+        keysym: u32,
+        state: ModMask,
+    },
+    ButtonPress {
+        window: Window,
+        x: i32,
+        button: ButtonIndex,
+    },
+    Expose(Window),
+    PropertyNotify {
+        window: Window,
+        atom: Atom,
+    },
+    ScreenChange,
+    ModelineTick,
+    ModuleOutput,
+    // SYSTEM_TRAY_REQUEST_DOCK for an icon window
+    TrayDock(Window),
+}
+
+// a connected monitor, named after its output (eDP-1, HDMI-1, ...)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Output {
+    pub name: String,
+    pub rect: Rect,
+    pub primary: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WindowAttributes {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+    pub override_redirect: bool,
+}
+
+// everything koan asks from the display server, so the window management
+// logic can run against X11 or against the in-memory fake used by the tests
+pub trait Backend {
+    fn root(&self) -> Window;
+    fn intern_atom(&self, name: &str) -> Atom;
+    fn flush(&self);
+
+    // None once there is nothing left to handle
+    fn next_event(&self) -> Option<Event>;
+    fn select_root_input(&self);
+    fn select_client_input(&self, window: Window);
+    fn grab_keys(&self, bindings: &[KeyBinding]);
+    fn spawn_timer(&self, interval: Duration);
+    fn waker(&self) -> Waker;
+
+    fn monitors(&self) -> Vec<Output>;
+    fn warp_pointer(&self, x: i32, y: i32);
+
+    fn window_attributes(&self, window: Window) -> Option<WindowAttributes>;
+    fn transient_for(&self, window: Window) -> Option<Window>;
+    // _NET_WM_NAME, or the legacy WM_NAME when a client does not set it
+    fn window_title(&self, window: Window) -> Option<String>;
+    // instance and class names from WM_CLASS
+    fn window_class(&self, window: Window) -> Vec<String>;
+    fn get_atoms(&self, window: Window, property: Atom) -> Vec<Atom>;
+    fn append_atom(&self, window: Window, property: Atom, value: Atom);
+    // UTF8_STRING lists, like _NET_DESKTOP_NAMES
+    fn get_strings(&self, window: Window, property: Atom) -> Vec<String>;
+    fn set_strings(&self, window: Window, property: Atom, values: &[String]);
+    fn set_cardinals(&self, window: Window, property: Atom, values: &[u32]);
+    // urgency flag of WM_HINTS
+    fn is_urgent(&self, window: Window) -> bool;
+    fn send_protocol(&self, window: Window, protocol: Atom);
+
+    // makes `tray` the system tray of the screen, false when another one
+    // already is
+    fn acquire_tray(&self, tray: Window) -> bool;
+    // embeds `icon` in `tray`, true when it was mapped and reparenting it
+    // causes an UnmapNotify
+    fn dock_icon(&self, tray: Window, icon: Window, background: &str) -> bool;
+    // gives a removed icon back to the root window
+    fn undock_icon(&self, icon: Window);
+    // the XEMBED_MAPPED flag of _XEMBED_INFO, true when the icon does not set it
+    fn xembed_mapped(&self, icon: Window) -> bool;
+
+    fn map(&self, window: Window);
+    fn map_raised(&self, window: Window);
+    fn unmap(&self, window: Window);
+    fn raise(&self, window: Window);
+    fn move_window(&self, window: Window, x: i32, y: i32);
+    fn resize_window(&self, window: Window, width: u32, height: u32);
+    fn move_resize(&self, window: Window, rect: Rect);
+    fn set_border_width(&self, window: Window, width: u32);
+    fn set_border_color(&self, window: Window, color: &str);
+    fn set_input_focus(&self, window: Window);
+
+    // override-redirect windows drawn by koan itself (modelines, tab bars)
+    fn create_bar(&self, rect: Rect, background: &str, clickable: bool) -> Window;
+    fn destroy_window(&self, window: Window);
+    fn clear(&self, window: Window);
+    fn fill_rect(&self, window: Window, rect: Rect, color: &str);
+    fn draw_text(&self, window: Window, x: i32, y: i32, text: &str, color: &str);
+    fn text_width(&self, text: &str) -> i32;
+    // y of the baseline that vertically centers a line of text in `height`
+    fn text_baseline(&self, height: u32) -> i32;
+}
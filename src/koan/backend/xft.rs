@@ -0,0 +1,231 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    ffi::CString,
+    mem,
+    os::raw::{c_int, c_ulong},
+    ptr,
+};
+
+use x11::{
+    xft::{self, XftColor, XftDraw, XftFont},
+    xlib::{self, Display, Visual, XErrorEvent},
+    xrender::XGlyphInfo,
+};
+
+use crate::koan::{layouts::Rect, Window};
+
+// antialiased UTF-8 text for the bars. Xft needs an Xlib display, so it gets
+// its own connection and every request drawing on a bar goes through it to
+// reach the server in order.
+//
+// The bars are created and destroyed on the x11rb connection, and the server
+// does not order requests across connections. Xorg::create_bar syncs before
+// Xft draws on a new bar, and `forget` syncs before Xorg destroys one, so no
+// drawing request arrives for a window that is not there. The error handler
+// installed here is process wide, it only logs
+pub struct Xft {
+    display: *mut Display,
+    visual: *mut Visual,
+    colormap: c_ulong,
+    fonts: Vec<*mut XftFont>, // the first one sets the line height, the rest are fallbacks
+
+    draws: RefCell<HashMap<Window, *mut XftDraw>>,
+    colors: RefCell<HashMap<String, XftColor>>,
+}
+
+impl Xft {
+    pub fn open(fonts: &[&str]) -> Option<Self> {
+        let display = unsafe { xlib::XOpenDisplay(ptr::null()) };
+        if display.is_null() {
+            return None;
+        }
+
+        unsafe { xlib::XSetErrorHandler(Some(log_error)) };
+        let screen = unsafe { xlib::XDefaultScreen(display) };
+
+        let fonts: Vec<*mut XftFont> = fonts
+            .iter()
+            .filter_map(|name| {
+                let name = CString::new(*name).ok()?;
+                let font = unsafe { xft::XftFontOpenName(display, screen, name.as_ptr()) };
+                (!font.is_null()).then_some(font)
+            })
+            .collect();
+
+        if fonts.is_empty() {
+            unsafe { xlib::XCloseDisplay(display) };
+            return None;
+        }
+
+        Some(Self {
+            display,
+            visual: unsafe { xlib::XDefaultVisual(display, screen) },
+            colormap: unsafe { xlib::XDefaultColormap(display, screen) },
+            fonts,
+            draws: RefCell::new(HashMap::new()),
+            colors: RefCell::new(HashMap::new()),
+        })
+    }
+
+    pub fn flush(&self) {
+        unsafe { xlib::XFlush(self.display) };
+    }
+
+    pub fn width(&self, text: &str) -> i32 {
+        self.runs(text)
+            .into_iter()
+            .map(|(font, run)| self.advance(font, run))
+            .sum()
+    }
+
+    // y of the baseline that centers a line in `height` pixels
+    pub fn baseline(&self, height: u32) -> i32 {
+        let font = unsafe { &*self.fonts[0] };
+        (height as i32 - (font.ascent + font.descent)) / 2 + font.ascent
+    }
+
+    pub fn draw(&self, window: Window, x: i32, y: i32, text: &str, color: &str) {
+        let Some(color) = self.color(color) else {
+            return;
+        };
+        let draw = self.drawable(window);
+
+        let mut x = x;
+        for (font, run) in self.runs(text) {
+            unsafe {
+                xft::XftDrawStringUtf8(draw, &color, font, x, y, run.as_ptr(), run.len() as c_int)
+            };
+            x += self.advance(font, run);
+        }
+    }
+
+    pub fn fill(&self, window: Window, rect: Rect, color: &str) {
+        if let Some(color) = self.color(color) {
+            let draw = self.drawable(window);
+            unsafe { xft::XftDrawRect(draw, &color, rect.x, rect.y, rect.w, rect.h) };
+        }
+    }
+
+    pub fn clear(&self, window: Window) {
+        unsafe { xlib::XClearArea(self.display, window as c_ulong, 0, 0, 0, 0, 0) };
+    }
+
+    // before the window is destroyed, waits until the server has handled
+    // everything drawn on it
+    pub fn forget(&self, window: Window) {
+        if let Some(draw) = self.draws.borrow_mut().remove(&window) {
+            unsafe { xft::XftDrawDestroy(draw) };
+        }
+        unsafe { xlib::XSync(self.display, xlib::False) };
+    }
+
+    fn drawable(&self, window: Window) -> *mut XftDraw {
+        *self
+            .draws
+            .borrow_mut()
+            .entry(window)
+            .or_insert_with(|| unsafe {
+                xft::XftDrawCreate(self.display, window as c_ulong, self.visual, self.colormap)
+            })
+    }
+
+    fn color(&self, name: &str) -> Option<XftColor> {
+        if let Some(&color) = self.colors.borrow().get(name) {
+            return Some(color);
+        }
+
+        let cname = CString::new(name).ok()?;
+        let mut color: XftColor = unsafe { mem::zeroed() };
+        let allocated = unsafe {
+            xft::XftColorAllocName(
+                self.display,
+                self.visual,
+                self.colormap,
+                cname.as_ptr(),
+                &mut color,
+            )
+        };
+        if allocated == 0 {
+            eprintln!("Could not allocate color {}", name);
+            return None;
+        }
+
+        self.colors.borrow_mut().insert(name.to_string(), color);
+        Some(color)
+    }
+
+    // first font that has the glyph, the main one draws the missing glyph box
+    fn font_for(&self, c: char) -> *mut XftFont {
+        self.fonts
+            .iter()
+            .copied()
+            .find(|&font| unsafe { xft::XftCharExists(self.display, font, c as u32) } != 0)
+            .unwrap_or(self.fonts[0])
+    }
+
+    // `text` split where the font drawing it changes
+    fn runs<'a>(&self, text: &'a str) -> Vec<(*mut XftFont, &'a str)> {
+        let mut runs = Vec::new();
+        let mut start = 0;
+        let mut current = None;
+
+        for (i, c) in text.char_indices() {
+            let font = self.font_for(c);
+            match current {
+                Some(prev) if prev != font => {
+                    runs.push((prev, &text[start..i]));
+                    start = i;
+                }
+                _ => {}
+            }
+            current = Some(font);
+        }
+        if let Some(font) = current {
+            runs.push((font, &text[start..]));
+        }
+        runs
+    }
+
+    fn advance(&self, font: *mut XftFont, text: &str) -> i32 {
+        let mut info: XGlyphInfo = unsafe { mem::zeroed() };
+        unsafe {
+            xft::XftTextExtentsUtf8(
+                self.display,
+                font,
+                text.as_ptr(),
+                text.len() as c_int,
+                &mut info,
+            )
+        };
+        info.xOff as i32
+    }
+}
+
+impl Drop for Xft {
+    fn drop(&mut self) {
+        unsafe {
+            for (_, draw) in self.draws.get_mut().drain() {
+                xft::XftDrawDestroy(draw);
+            }
+            for (_, mut color) in self.colors.get_mut().drain() {
+                xft::XftColorFree(self.display, self.visual, self.colormap, &mut color);
+            }
+            for &font in &self.fonts {
+                xft::XftFontClose(self.display, font);
+            }
+            xlib::XCloseDisplay(self.display);
+        }
+    }
+}
+
+// the default handler exits the process, a bar destroyed while one of its
+// draws is still queued is not worth that
+unsafe extern "C" fn log_error(_: *mut Display, event: *mut XErrorEvent) -> c_int {
+    let event = unsafe { &*event };
+    eprintln!(
+        "Xft error: code {} in request {}",
+        event.error_code, event.request_code
+    );
+    0
+}
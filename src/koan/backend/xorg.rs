@@ -0,0 +1,906 @@
+use std::{cell::RefCell, collections::HashMap, sync::Arc, thread, time::Duration};
+
+use x11rb::{
+    connection::Connection,
+    errors::ConnectionError,
+    protocol::{
+        randr::{ConnectionExt as _, NotifyMask},
+        xinerama::ConnectionExt as _,
+        xproto::{
+            AtomEnum, ButtonIndex, ChangeGCAux, ChangeWindowAttributesAux, ClientMessageEvent,
+            ConfigureWindowAux, ConnectionExt as _, CreateGCAux, CreateWindowAux, EventMask,
+            Gcontext, Grab, GrabMode, InputFocus, Keysym, MapState, ModMask, PropMode, Rectangle,
+            SetMode, StackMode, WindowClass,
+        },
+        Event as XEvent,
+    },
+    rust_connection::RustConnection,
+    wrapper::ConnectionExt as _,
+    COPY_DEPTH_FROM_PARENT, COPY_FROM_PARENT, CURRENT_TIME, NONE,
+};
+
+use super::{xft::Xft, Atom, Backend, Event, Output, Waker, WindowAttributes};
+use crate::koan::{
+    config::{KeyBinding, FONTS},
+    layouts::Rect,
+    KoanWMError, Window,
+};
+
+const CHAR_WIDTH: i32 = 8;
+const CHAR_HEIGHT: i32 = 13;
+
+// system tray and XEmbed opcodes
+const SYSTEM_TRAY_REQUEST_DOCK: u32 = 0;
+const SYSTEM_TRAY_ORIENTATION_HORZ: u32 = 0;
+const XEMBED_EMBEDDED_NOTIFY: u32 = 0;
+const XEMBED_VERSION: u32 = 0;
+const XEMBED_MAPPED: u32 = 1 << 0;
+
+// interned in one round-trip at startup instead of one per lookup
+const PRELOADED_ATOMS: &[&str] = &[
+    "WM_PROTOCOLS",
+    "WM_DELETE_WINDOW",
+    "MINIWM_UPDATE_BAR",
+    "MINIWM_MODULE_OUTPUT",
+    "MANAGER",
+    "_NET_SYSTEM_TRAY_OPCODE",
+    "_NET_SYSTEM_TRAY_ORIENTATION",
+    "_XEMBED",
+    "_XEMBED_INFO",
+    "UTF8_STRING",
+    "_NET_WM_NAME",
+    "_NET_NUMBER_OF_DESKTOPS",
+    "_NET_CURRENT_DESKTOP",
+    "_NET_DESKTOP_NAMES",
+    "_NET_WM_DESKTOP",
+    "_NET_WM_STATE",
+    "_NET_WM_STATE_ABOVE",
+    "_NET_WM_WINDOW_TYPE",
+    "_NET_WM_WINDOW_TYPE_SPLASH",
+    "_NET_WM_WINDOW_TYPE_TOOLBAR",
+    "_NET_WM_WINDOW_TYPE_MENU",
+    "_NET_WM_WINDOW_TYPE_DROPDOWN_MENU",
+    "_NET_WM_WINDOW_TYPE_POPUP_MENU",
+    "_NET_WM_WINDOW_TYPE_TOOLTIP",
+    "_NET_WM_WINDOW_TYPE_NOTIFICATION",
+    "_NET_WM_WINDOW_TYPE_COMBO",
+    "_NET_WM_WINDOW_TYPE_DND",
+];
+
+pub struct Xorg {
+    conn: Arc<RustConnection>,
+    root: Window,
+    screen_num: usize,
+    wake: Window, // unmapped window the modeline timer sends its ticks to
+    randr: bool,  // monitors come from RandR 1.5 instead of Xinerama
+    colormap: u32,
+    gc: Gcontext, // shared by the modelines and tab bars
+    char_width: i32,
+    char_ascent: i32,
+    xft: Option<Xft>, // the core font above is only used without it
+
+    min_keycode: u8,
+    keysyms_per_keycode: usize,
+    keysyms: Vec<Keysym>,
+
+    atoms: RefCell<HashMap<String, Atom>>,
+    colors: RefCell<HashMap<String, u32>>,
+    wm_protocols: Atom,
+    update_bar: Atom,
+    module_output: Atom,
+    tray_opcode: Atom,
+}
+
+impl Xorg {
+    pub fn connect() -> Result<Self, KoanWMError> {
+        let (conn, screen_num) = x11rb::connect(None)?;
+        let screen = &conn.setup().roots[screen_num];
+        let root = screen.root;
+        let colormap = screen.default_colormap;
+
+        let font = conn.generate_id()?;
+        conn.open_font(font, b"fixed")?;
+        let gc = conn.generate_id()?;
+        conn.create_gc(gc, root, &CreateGCAux::new().font(font))?;
+        let (char_width, char_ascent) = conn
+            .query_font(font)?
+            .reply()
+            .map_or((CHAR_WIDTH, CHAR_HEIGHT * 3 / 4), |f| {
+                (f.max_bounds.character_width as i32, f.font_ascent as i32)
+            });
+
+        let xft = Xft::open(FONTS);
+        if xft.is_none() {
+            eprintln!("Could not load the configured fonts, using the core font");
+        }
+
+        let wake = conn.generate_id()?;
+        conn.create_window(
+            0,
+            wake,
+            root,
+            -1,
+            -1,
+            1,
+            1,
+            0,
+            WindowClass::INPUT_ONLY,
+            COPY_FROM_PARENT,
+            &CreateWindowAux::new(),
+        )?;
+
+        let randr = conn
+            .randr_query_version(1, 5)?
+            .reply()
+            .is_ok_and(|v| (v.major_version, v.minor_version) >= (1, 5));
+
+        let min_keycode = conn.setup().min_keycode;
+        let max_keycode = conn.setup().max_keycode;
+        let mapping = conn
+            .get_keyboard_mapping(min_keycode, max_keycode - min_keycode + 1)?
+            .reply()?;
+
+        let cookies = PRELOADED_ATOMS
+            .iter()
+            .map(|name| conn.intern_atom(false, name.as_bytes()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut atoms = HashMap::new();
+        for (name, cookie) in PRELOADED_ATOMS.iter().zip(cookies) {
+            atoms.insert(name.to_string(), cookie.reply()?.atom);
+        }
+
+        Ok(Self {
+            conn: Arc::new(conn),
+            root,
+            screen_num,
+            wake,
+            randr,
+            colormap,
+            gc,
+            char_width,
+            char_ascent,
+            xft,
+            min_keycode,
+            keysyms_per_keycode: mapping.keysyms_per_keycode.max(1) as usize,
+            keysyms: mapping.keysyms,
+            wm_protocols: atoms["WM_PROTOCOLS"],
+            update_bar: atoms["MINIWM_UPDATE_BAR"],
+            module_output: atoms["MINIWM_MODULE_OUTPUT"],
+            tray_opcode: atoms["_NET_SYSTEM_TRAY_OPCODE"],
+            atoms: RefCell::new(atoms),
+            colors: RefCell::new(HashMap::new()),
+        })
+    }
+
+    // requests without a reply report their errors through the event queue,
+    // only a broken connection shows up here
+    fn send<T>(&self, request: Result<T, ConnectionError>) {
+        if let Err(e) = request {
+            eprintln!("X11 connection error: {}", e);
+        }
+    }
+
+    fn pixel(&self, color: &str) -> u32 {
+        if let Some(&pixel) = self.colors.borrow().get(color) {
+            return pixel;
+        }
+
+        let Ok(css_color) = csscolorparser::parse(color) else {
+            return 0;
+        };
+        let [r, g, b, _] = css_color.to_rgba8();
+        let pixel = self
+            .conn
+            .alloc_color(
+                self.colormap,
+                r as u16 * 257,
+                g as u16 * 257,
+                b as u16 * 257,
+            )
+            .ok()
+            .and_then(|cookie| cookie.reply().ok())
+            .map_or(0, |reply| reply.pixel);
+
+        self.colors.borrow_mut().insert(color.to_string(), pixel);
+        pixel
+    }
+
+    fn keysym(&self, keycode: u8) -> Keysym {
+        let idx = keycode.saturating_sub(self.min_keycode) as usize * self.keysyms_per_keycode;
+        self.keysyms.get(idx).copied().unwrap_or(0)
+    }
+
+    fn keycode(&self, keysym: Keysym) -> Option<u8> {
+        let idx = self.keysyms.iter().position(|&k| k == keysym)?;
+        Some(self.min_keycode + (idx / self.keysyms_per_keycode) as u8)
+    }
+
+    fn randr_monitors(&self) -> Option<Vec<Output>> {
+        let monitors = self
+            .conn
+            .randr_get_monitors(self.root, true)
+            .ok()?
+            .reply()
+            .ok()?
+            .monitors;
+
+        // ask for every name before reading the first answer
+        let names = monitors
+            .iter()
+            .map(|m| self.conn.get_atom_name(m.name).ok())
+            .collect::<Vec<_>>();
+
+        let outputs = monitors
+            .iter()
+            .zip(names)
+            .enumerate()
+            .map(|(i, (m, cookie))| Output {
+                name: cookie.and_then(|c| c.reply().ok()).map_or_else(
+                    || format!("monitor-{}", i),
+                    |r| String::from_utf8_lossy(&r.name).into_owned(),
+                ),
+                rect: Rect {
+                    x: m.x as i32,
+                    y: m.y as i32,
+                    w: m.width as u32,
+                    h: m.height as u32,
+                },
+                primary: m.primary,
+            })
+            .collect();
+        Some(outputs)
+    }
+
+    fn property(&self, window: Window, property: Atom, kind: AtomEnum) -> Option<Vec<u32>> {
+        let reply = self
+            .conn
+            .get_property(false, window, property, kind, 0, 32)
+            .ok()?
+            .reply()
+            .ok()?;
+        if reply.type_ == NONE {
+            return None;
+        }
+        Some(reply.value32()?.collect())
+    }
+}
+
+impl Backend for Xorg {
+    fn root(&self) -> Window {
+        self.root
+    }
+
+    fn intern_atom(&self, name: &str) -> Atom {
+        if let Some(&atom) = self.atoms.borrow().get(name) {
+            return atom;
+        }
+
+        let atom = self
+            .conn
+            .intern_atom(false, name.as_bytes())
+            .ok()
+            .and_then(|cookie| cookie.reply().ok())
+            .map_or(NONE, |reply| reply.atom);
+        self.atoms.borrow_mut().insert(name.to_string(), atom);
+        atom
+    }
+
+    fn flush(&self) {
+        self.send(self.conn.flush());
+        if let Some(xft) = &self.xft {
+            xft.flush();
+        }
+    }
+
+    fn next_event(&self) -> Option<Event> {
+        loop {
+            self.flush();
+            let event = match self.conn.wait_for_event() {
+                Ok(event) => event,
+                Err(e) => {
+                    eprintln!("X11 connection lost: {}", e);
+                    return None;
+                }
+            };
+
+            let translated = match event {
+                XEvent::Error(e) => {
+                    eprintln!(
+                        "X11 Error: {:?} in {}, value={}",
+                        e.error_kind,
+                        e.request_name.unwrap_or("unknown request"),
+                        e.bad_value
+                    );
+                    None
+                }
+                XEvent::ClientMessage(ev) if ev.type_ == self.update_bar => {
+                    Some(Event::ModelineTick)
+                }
+                XEvent::ClientMessage(ev) if ev.type_ == self.module_output => {
+                    Some(Event::ModuleOutput)
+                }
+                XEvent::ClientMessage(ev) if ev.type_ == self.tray_opcode => {
+                    let data = ev.data.as_data32();
+                    (data[1] == SYSTEM_TRAY_REQUEST_DOCK).then_some(Event::TrayDock(data[2]))
+                }
+                XEvent::Expose(ev) => Some(Event::Expose(ev.window)),
+                XEvent::PropertyNotify(ev) => Some(Event::PropertyNotify {
+                    window: ev.window,
+                    atom: ev.atom,
+                }),
+                XEvent::RandrScreenChangeNotify(_) | XEvent::RandrNotify(_) => {
+                    Some(Event::ScreenChange)
+                }
+                XEvent::ConfigureNotify(ev) => {
+                    (!self.randr && ev.window == self.root).then_some(Event::ScreenChange)
+                }
+                XEvent::MapRequest(ev) => Some(Event::MapRequest(ev.window)),
+                XEvent::UnmapNotify(ev) => Some(Event::UnmapNotify(ev.window)),
+                XEvent::DestroyNotify(ev) => Some(Event::DestroyNotify(ev.window)),
+                XEvent::ButtonPress(ev) => Some(Event::ButtonPress {
+                    window: ev.event,
+                    x: ev.event_x as i32,
+                    button: ButtonIndex::from(ev.detail),
+                }),
+                XEvent::EnterNotify(ev) => Some(Event::EnterNotify {
+                    window: ev.event,
+                    x_root: ev.root_x as i32,
+                    y_root: ev.root_y as i32,
+                }),
+                XEvent::KeyPress(ev) => Some(Event::KeyPress {
+                    keysym: self.keysym(ev.detail),
+                    state: ModMask::from(u16::from(ev.state)),
+                }),
+                _ => None,
+            };
+
+            if translated.is_some() {
+                return translated;
+            }
+        }
+    }
+
+    fn select_root_input(&self) {
+        let mask = EventMask::SUBSTRUCTURE_REDIRECT
+            | EventMask::SUBSTRUCTURE_NOTIFY
+            | EventMask::FOCUS_CHANGE
+            | EventMask::ENTER_WINDOW
+            | EventMask::STRUCTURE_NOTIFY
+            | EventMask::PROPERTY_CHANGE;
+        let request = self.conn.change_window_attributes(
+            self.root,
+            &ChangeWindowAttributesAux::new().event_mask(mask),
+        );
+
+        // fails when another window manager owns the root window
+        if let Ok(cookie) = request
+            && let Err(e) = cookie.check()
+        {
+            eprintln!("Could not select input on the root window: {}", e);
+        }
+
+        if self.randr {
+            let mask =
+                NotifyMask::SCREEN_CHANGE | NotifyMask::OUTPUT_CHANGE | NotifyMask::CRTC_CHANGE;
+            self.send(self.conn.randr_select_input(self.root, mask));
+        }
+    }
+
+    fn select_client_input(&self, window: Window) {
+        let aux = ChangeWindowAttributesAux::new().event_mask(
+            EventMask::ENTER_WINDOW | EventMask::FOCUS_CHANGE | EventMask::PROPERTY_CHANGE,
+        );
+        self.send(self.conn.change_window_attributes(window, &aux));
+    }
+
+    fn grab_keys(&self, bindings: &[KeyBinding]) {
+        self.send(self.conn.ungrab_key(Grab::ANY, self.root, ModMask::ANY));
+
+        // send every grab first, then wait for all the answers at once
+        let mut cookies = Vec::new();
+        for binding in bindings {
+            let Some(keycode) = self.keycode(binding.keysym) else {
+                eprintln!("Warning: no keycode for keysym {}", binding.keysym);
+                continue;
+            };
+            let request = self.conn.grab_key(
+                false,
+                self.root,
+                binding.mask(),
+                keycode,
+                GrabMode::ASYNC,
+                GrabMode::ASYNC,
+            );
+            if let Ok(cookie) = request {
+                cookies.push((binding, cookie));
+            }
+        }
+
+        for (binding, cookie) in cookies {
+            if cookie.check().is_err() {
+                eprintln!("Warning: grab failed for keysym {}", binding.keysym);
+            }
+        }
+    }
+
+    // the event loop blocks in wait_for_event, the timer wakes it up with a
+    // client message sent through the same connection
+    fn spawn_timer(&self, interval: Duration) {
+        let conn = Arc::clone(&self.conn);
+        let window = self.wake;
+        let update_bar = self.update_bar;
+        thread::spawn(move || loop {
+            thread::sleep(interval);
+            let event = ClientMessageEvent::new(32, window, update_bar, [0u32; 5]);
+            let sent = conn
+                .send_event(false, window, EventMask::NO_EVENT, event)
+                .and_then(|_| conn.flush());
+            if sent.is_err() {
+                return;
+            }
+        });
+    }
+
+    fn waker(&self) -> Waker {
+        let conn = Arc::clone(&self.conn);
+        let window = self.wake;
+        let module_output = self.module_output;
+        Box::new(move || {
+            let event = ClientMessageEvent::new(32, window, module_output, [0u32; 5]);
+            conn.send_event(false, window, EventMask::NO_EVENT, event)
+                .and_then(|_| conn.flush())
+                .is_ok()
+        })
+    }
+
+    fn monitors(&self) -> Vec<Output> {
+        if self.randr
+            && let Some(outputs) = self.randr_monitors()
+            && !outputs.is_empty()
+        {
+            return outputs;
+        }
+
+        let screens = self
+            .conn
+            .xinerama_query_screens()
+            .ok()
+            .and_then(|cookie| cookie.reply().ok())
+            .map(|reply| reply.screen_info)
+            .unwrap_or_default();
+
+        if screens.is_empty() {
+            let root = self.window_attributes(self.root).unwrap_or_default();
+            return vec![Output {
+                name: "screen-0".to_string(),
+                primary: true,
+                rect: Rect {
+                    x: 0,
+                    y: 0,
+                    w: root.width as u32,
+                    h: root.height as u32,
+                },
+            }];
+        }
+
+        screens
+            .iter()
+            .enumerate()
+            .map(|(i, screen)| Output {
+                name: format!("screen-{}", i),
+                primary: i == 0,
+                rect: Rect {
+                    x: screen.x_org as i32,
+                    y: screen.y_org as i32,
+                    w: screen.width as u32,
+                    h: screen.height as u32,
+                },
+            })
+            .collect()
+    }
+
+    fn warp_pointer(&self, x: i32, y: i32) {
+        self.send(
+            self.conn
+                .warp_pointer(NONE, self.root, 0, 0, 0, 0, x as i16, y as i16),
+        );
+        self.flush();
+    }
+
+    fn window_attributes(&self, window: Window) -> Option<WindowAttributes> {
+        // both requests are in flight before waiting on the first reply
+        let attrs = self.conn.get_window_attributes(window).ok()?;
+        let geometry = self.conn.get_geometry(window).ok()?;
+        let attrs = attrs.reply().ok()?;
+        let geometry = geometry.reply().ok()?;
+
+        Some(WindowAttributes {
+            x: geometry.x as i32,
+            y: geometry.y as i32,
+            width: geometry.width as i32,
+            height: geometry.height as i32,
+            override_redirect: attrs.override_redirect,
+        })
+    }
+
+    fn transient_for(&self, window: Window) -> Option<Window> {
+        self.property(window, AtomEnum::WM_TRANSIENT_FOR.into(), AtomEnum::WINDOW)?
+            .first()
+            .copied()
+    }
+
+    fn window_title(&self, window: Window) -> Option<String> {
+        let utf8 = self.intern_atom("UTF8_STRING");
+        let net_wm_name = self.intern_atom("_NET_WM_NAME");
+
+        // both asked at once, most clients set the first one
+        let net = self
+            .conn
+            .get_property(false, window, net_wm_name, utf8, 0, 1024);
+        let legacy =
+            self.conn
+                .get_property(false, window, AtomEnum::WM_NAME, AtomEnum::ANY, 0, 1024);
+
+        if let Some(reply) = net.ok().and_then(|cookie| cookie.reply().ok())
+            && reply.type_ == utf8
+        {
+            return Some(String::from_utf8_lossy(&reply.value).into_owned());
+        }
+
+        let reply = legacy.ok()?.reply().ok()?;
+        match reply.type_ {
+            NONE => None,
+            // Latin-1, its bytes are the first 256 code points
+            t if t == u32::from(AtomEnum::STRING) => {
+                Some(reply.value.iter().map(|&b| b as char).collect())
+            }
+            _ => Some(String::from_utf8_lossy(&reply.value).into_owned()),
+        }
+    }
+
+    fn window_class(&self, window: Window) -> Vec<String> {
+        let Some(reply) = self
+            .conn
+            .get_property(false, window, AtomEnum::WM_CLASS, AtomEnum::STRING, 0, 1024)
+            .ok()
+            .and_then(|cookie| cookie.reply().ok())
+        else {
+            return Vec::new();
+        };
+        reply
+            .value
+            .split(|&b| b == 0)
+            .filter(|name| !name.is_empty())
+            .map(|name| String::from_utf8_lossy(name).into_owned())
+            .collect()
+    }
+
+    fn get_atoms(&self, window: Window, property: Atom) -> Vec<Atom> {
+        self.property(window, property, AtomEnum::ATOM)
+            .unwrap_or_default()
+    }
+
+    fn append_atom(&self, window: Window, property: Atom, value: Atom) {
+        self.send(self.conn.change_property32(
+            PropMode::APPEND,
+            window,
+            property,
+            AtomEnum::ATOM,
+            &[value],
+        ));
+    }
+
+    fn send_protocol(&self, window: Window, protocol: Atom) {
+        let event = ClientMessageEvent::new(
+            32,
+            window,
+            self.wm_protocols,
+            [protocol, CURRENT_TIME, 0, 0, 0],
+        );
+        self.send(
+            self.conn
+                .send_event(false, window, EventMask::NO_EVENT, event),
+        );
+        self.flush();
+    }
+
+    fn acquire_tray(&self, tray: Window) -> bool {
+        let selection = self.intern_atom(&format!("_NET_SYSTEM_TRAY_S{}", self.screen_num));
+        self.send(self.conn.set_selection_owner(tray, selection, CURRENT_TIME));
+        let owner = self
+            .conn
+            .get_selection_owner(selection)
+            .ok()
+            .and_then(|cookie| cookie.reply().ok())
+            .map(|reply| reply.owner);
+        if owner != Some(tray) {
+            return false;
+        }
+
+        let orientation = self.intern_atom("_NET_SYSTEM_TRAY_ORIENTATION");
+        self.send(self.conn.change_property32(
+            PropMode::REPLACE,
+            tray,
+            orientation,
+            AtomEnum::CARDINAL,
+            &[SYSTEM_TRAY_ORIENTATION_HORZ],
+        ));
+
+        // applications started before koan wait for this to dock their icons
+        let manager = self.intern_atom("MANAGER");
+        let event = ClientMessageEvent::new(
+            32,
+            self.root,
+            manager,
+            [CURRENT_TIME, selection, tray, 0, 0],
+        );
+        self.send(
+            self.conn
+                .send_event(false, self.root, EventMask::STRUCTURE_NOTIFY, event),
+        );
+        self.flush();
+        true
+    }
+
+    fn dock_icon(&self, tray: Window, icon: Window, background: &str) -> bool {
+        let mapped = self
+            .conn
+            .get_window_attributes(icon)
+            .ok()
+            .and_then(|cookie| cookie.reply().ok())
+            .is_some_and(|a| a.map_state != MapState::UNMAPPED);
+
+        // back to the root window if koan goes away before the icon
+        self.send(self.conn.change_save_set(SetMode::INSERT, icon));
+        self.send(self.conn.reparent_window(icon, tray, 0, 0));
+        // selected after reparenting, the unmap it caused is reported once
+        let aux = ChangeWindowAttributesAux::new()
+            .background_pixel(self.pixel(background))
+            .event_mask(EventMask::STRUCTURE_NOTIFY | EventMask::PROPERTY_CHANGE);
+        self.send(self.conn.change_window_attributes(icon, &aux));
+
+        let xembed = self.intern_atom("_XEMBED");
+        let event = ClientMessageEvent::new(
+            32,
+            icon,
+            xembed,
+            [
+                CURRENT_TIME,
+                XEMBED_EMBEDDED_NOTIFY,
+                0,
+                tray,
+                XEMBED_VERSION,
+            ],
+        );
+        self.send(
+            self.conn
+                .send_event(false, icon, EventMask::NO_EVENT, event),
+        );
+        mapped
+    }
+
+    fn undock_icon(&self, icon: Window) {
+        self.send(self.conn.reparent_window(icon, self.root, 0, 0));
+        self.send(self.conn.change_save_set(SetMode::DELETE, icon));
+        self.flush();
+    }
+
+    fn xembed_mapped(&self, icon: Window) -> bool {
+        let info = self.intern_atom("_XEMBED_INFO");
+        // version, then flags
+        self.property(icon, info, AtomEnum::ANY)
+            .and_then(|values| values.get(1).copied())
+            .is_none_or(|flags| flags & XEMBED_MAPPED != 0)
+    }
+
+    fn get_strings(&self, window: Window, property: Atom) -> Vec<String> {
+        let utf8 = self.intern_atom("UTF8_STRING");
+        let Some(reply) = self
+            .conn
+            .get_property(false, window, property, utf8, 0, 1024)
+            .ok()
+            .and_then(|cookie| cookie.reply().ok())
+        else {
+            return Vec::new();
+        };
+        if reply.type_ != utf8 {
+            return Vec::new();
+        }
+
+        // every string ends with a null byte, including the last one
+        let value = reply.value.strip_suffix(&[0]).unwrap_or(&reply.value);
+        value
+            .split(|&b| b == 0)
+            .map(|s| String::from_utf8_lossy(s).into_owned())
+            .collect()
+    }
+
+    fn set_strings(&self, window: Window, property: Atom, values: &[String]) {
+        let mut data = Vec::new();
+        for value in values {
+            data.extend_from_slice(value.as_bytes());
+            data.push(0);
+        }
+        let utf8 = self.intern_atom("UTF8_STRING");
+        self.send(
+            self.conn
+                .change_property8(PropMode::REPLACE, window, property, utf8, &data),
+        );
+    }
+
+    fn set_cardinals(&self, window: Window, property: Atom, values: &[u32]) {
+        self.send(self.conn.change_property32(
+            PropMode::REPLACE,
+            window,
+            property,
+            AtomEnum::CARDINAL,
+            values,
+        ));
+    }
+
+    fn is_urgent(&self, window: Window) -> bool {
+        const URGENCY_HINT: u32 = 1 << 8;
+        self.property(window, AtomEnum::WM_HINTS.into(), AtomEnum::WM_HINTS)
+            .and_then(|hints| hints.first().copied())
+            .is_some_and(|flags| flags & URGENCY_HINT != 0)
+    }
+
+    fn map(&self, window: Window) {
+        self.send(self.conn.map_window(window));
+    }
+
+    fn map_raised(&self, window: Window) {
+        self.raise(window);
+        self.map(window);
+    }
+
+    fn unmap(&self, window: Window) {
+        self.send(self.conn.unmap_window(window));
+    }
+
+    fn raise(&self, window: Window) {
+        let aux = ConfigureWindowAux::new().stack_mode(StackMode::ABOVE);
+        self.send(self.conn.configure_window(window, &aux));
+    }
+
+    fn move_window(&self, window: Window, x: i32, y: i32) {
+        let aux = ConfigureWindowAux::new().x(x).y(y);
+        self.send(self.conn.configure_window(window, &aux));
+    }
+
+    fn resize_window(&self, window: Window, width: u32, height: u32) {
+        let aux = ConfigureWindowAux::new().width(width).height(height);
+        self.send(self.conn.configure_window(window, &aux));
+    }
+
+    fn move_resize(&self, window: Window, rect: Rect) {
+        let aux = ConfigureWindowAux::new()
+            .x(rect.x)
+            .y(rect.y)
+            .width(rect.w)
+            .height(rect.h);
+        self.send(self.conn.configure_window(window, &aux));
+    }
+
+    fn set_border_width(&self, window: Window, width: u32) {
+        let aux = ConfigureWindowAux::new().border_width(width);
+        self.send(self.conn.configure_window(window, &aux));
+    }
+
+    fn set_border_color(&self, window: Window, color: &str) {
+        let aux = ChangeWindowAttributesAux::new().border_pixel(self.pixel(color));
+        self.send(self.conn.change_window_attributes(window, &aux));
+        self.flush();
+    }
+
+    fn set_input_focus(&self, window: Window) {
+        self.send(
+            self.conn
+                .set_input_focus(InputFocus::POINTER_ROOT, window, CURRENT_TIME),
+        );
+    }
+
+    fn create_bar(&self, rect: Rect, background: &str, clickable: bool) -> Window {
+        let Ok(window) = self.conn.generate_id() else {
+            eprintln!("Could not allocate an id for a bar window");
+            return NONE;
+        };
+
+        let mut event_mask = EventMask::EXPOSURE;
+        if clickable {
+            event_mask |= EventMask::BUTTON_PRESS;
+        }
+        let aux = CreateWindowAux::new()
+            .background_pixel(self.pixel(background))
+            .event_mask(event_mask)
+            .override_redirect(1);
+
+        self.send(self.conn.create_window(
+            COPY_DEPTH_FROM_PARENT,
+            window,
+            self.root,
+            rect.x as i16,
+            rect.y as i16,
+            rect.w as u16,
+            rect.h as u16,
+            0,
+            WindowClass::INPUT_OUTPUT,
+            COPY_FROM_PARENT,
+            &aux,
+        ));
+
+        // Xft draws from its own connection, the window has to exist by then
+        if self.xft.is_some()
+            && let Err(e) = self.conn.sync()
+        {
+            eprintln!("Could not create a bar window: {}", e);
+        }
+        window
+    }
+
+    fn destroy_window(&self, window: Window) {
+        if let Some(xft) = &self.xft {
+            xft.forget(window);
+        }
+        self.send(self.conn.destroy_window(window));
+    }
+
+    fn clear(&self, window: Window) {
+        match &self.xft {
+            Some(xft) => xft.clear(window),
+            None => self.send(self.conn.clear_area(false, window, 0, 0, 0, 0)),
+        }
+    }
+
+    fn fill_rect(&self, window: Window, rect: Rect, color: &str) {
+        if let Some(xft) = &self.xft {
+            return xft.fill(window, rect, color);
+        }
+
+        let gc = ChangeGCAux::new().foreground(self.pixel(color));
+        self.send(self.conn.change_gc(self.gc, &gc));
+        let rectangle = Rectangle {
+            x: rect.x as i16,
+            y: rect.y as i16,
+            width: rect.w as u16,
+            height: rect.h as u16,
+        };
+        self.send(self.conn.poly_fill_rectangle(window, self.gc, &[rectangle]));
+    }
+
+    fn draw_text(&self, window: Window, x: i32, y: i32, text: &str, color: &str) {
+        if let Some(xft) = &self.xft {
+            return xft.draw(window, x, y, text, color);
+        }
+
+        let gc = ChangeGCAux::new().foreground(self.pixel(color));
+        self.send(self.conn.change_gc(self.gc, &gc));
+
+        // TEXTITEM8: length, delta, then at most 254 bytes of text
+        let mut items = Vec::with_capacity(text.len() + 2);
+        for chunk in text.as_bytes().chunks(254) {
+            items.push(chunk.len() as u8);
+            items.push(0);
+            items.extend_from_slice(chunk);
+        }
+        self.send(
+            self.conn
+                .poly_text8(window, self.gc, x as i16, y as i16, &items),
+        );
+    }
+
+    fn text_width(&self, text: &str) -> i32 {
+        match &self.xft {
+            Some(xft) => xft.width(text),
+            // the core font is fixed width
+            None => text.len() as i32 * self.char_width,
+        }
+    }
+
+    fn text_baseline(&self, height: u32) -> i32 {
+        match &self.xft {
+            Some(xft) => xft.baseline(height),
+            None => (height as i32 - CHAR_HEIGHT) / 2 + self.char_ascent,
+        }
+    }
+}
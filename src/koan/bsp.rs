@@ -0,0 +1,454 @@
+use super::{
+    layouts::{split_height, split_width, Layout, LayoutParams, Rect},
+    window::Direction,
+    KoanWM, Window,
+};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Split {
+    Vertical,   // first | second
+    Horizontal, // first above second
+}
+
+pub enum Node {
+    Leaf(Window),
+    Split {
+        split: Split,
+        ratio: f32,
+        first: Box<Node>,
+        second: Box<Node>,
+    },
+}
+
+#[derive(Default)]
+pub struct BspTree {
+    pub root: Option<Node>,
+    pub presel: Option<Direction>,
+}
+
+impl Node {
+    fn is_leaf(&self, window: Window) -> bool {
+        matches!(self, Node::Leaf(w) if *w == window)
+    }
+
+    fn contains(&self, window: Window) -> bool {
+        match self {
+            Node::Leaf(w) => *w == window,
+            Node::Split { first, second, .. } => first.contains(window) || second.contains(window),
+        }
+    }
+
+    fn count(&self) -> usize {
+        match self {
+            Node::Leaf(_) => 1,
+            Node::Split { first, second, .. } => first.count() + second.count(),
+        }
+    }
+
+    fn leaves(&self, out: &mut Vec<Window>) {
+        match self {
+            Node::Leaf(w) => out.push(*w),
+            Node::Split { first, second, .. } => {
+                first.leaves(out);
+                second.leaves(out);
+            }
+        }
+    }
+
+    fn insert(&mut self, target: Window, window: Window, dir: Direction) -> bool {
+        match self {
+            Node::Leaf(w) if *w == target => {
+                let (split, new_first) = match dir {
+                    Direction::Left => (Split::Vertical, true),
+                    Direction::Right => (Split::Vertical, false),
+                    Direction::Up => (Split::Horizontal, true),
+                    Direction::Down => (Split::Horizontal, false),
+                };
+
+                let old = Node::Leaf(*w);
+                let new = Node::Leaf(window);
+                let (first, second) = if new_first { (new, old) } else { (old, new) };
+
+                *self = Node::Split {
+                    split,
+                    ratio: 0.5,
+                    first: Box::new(first),
+                    second: Box::new(second),
+                };
+                true
+            }
+            Node::Leaf(_) => false,
+            Node::Split { first, second, .. } => {
+                first.insert(target, window, dir) || second.insert(target, window, dir)
+            }
+        }
+    }
+
+    fn remove(self, window: Window) -> Option<Node> {
+        match self {
+            Node::Leaf(w) if w == window => None,
+            Node::Leaf(w) => Some(Node::Leaf(w)),
+            Node::Split {
+                split,
+                ratio,
+                first,
+                second,
+            } => match (first.remove(window), second.remove(window)) {
+                (Some(first), Some(second)) => Some(Node::Split {
+                    split,
+                    ratio,
+                    first: Box::new(first),
+                    second: Box::new(second),
+                }),
+                (Some(node), None) | (None, Some(node)) => Some(node),
+                (None, None) => None,
+            },
+        }
+    }
+
+    // the split node that directly holds the leaf of `window`
+    fn parent_of(&mut self, window: Window) -> Option<&mut Node> {
+        let is_parent = match self {
+            Node::Split { first, second, .. } => first.is_leaf(window) || second.is_leaf(window),
+            Node::Leaf(_) => false,
+        };
+
+        if is_parent {
+            return Some(self);
+        }
+
+        match self {
+            Node::Split { first, second, .. } => {
+                if first.contains(window) {
+                    first.parent_of(window)
+                } else {
+                    second.parent_of(window)
+                }
+            }
+            Node::Leaf(_) => None,
+        }
+    }
+
+    fn rotate(&mut self, clockwise: bool) {
+        if let Node::Split {
+            split,
+            ratio,
+            first,
+            second,
+        } = self
+        {
+            // clockwise: left goes to the top, top goes to the right
+            if (*split == Split::Horizontal) == clockwise {
+                std::mem::swap(first, second);
+                *ratio = 1.0 - *ratio;
+            }
+            *split = match split {
+                Split::Vertical => Split::Horizontal,
+                Split::Horizontal => Split::Vertical,
+            };
+            first.rotate(clockwise);
+            second.rotate(clockwise);
+        }
+    }
+
+    fn flip(&mut self, axis: Split) {
+        if let Node::Split {
+            split,
+            ratio,
+            first,
+            second,
+        } = self
+        {
+            if *split == axis {
+                std::mem::swap(first, second);
+                *ratio = 1.0 - *ratio;
+            }
+            first.flip(axis);
+            second.flip(axis);
+        }
+    }
+
+    fn balance(&mut self) {
+        if let Node::Split {
+            ratio,
+            first,
+            second,
+            ..
+        } = self
+        {
+            first.balance();
+            second.balance();
+            let left = first.count() as f32;
+            *ratio = left / (left + second.count() as f32);
+        }
+    }
+
+    fn geometry(&self, area: Rect, gaps: u32, out: &mut Vec<(Window, Rect)>) {
+        match self {
+            Node::Leaf(win) => out.push((*win, area)),
+            Node::Split {
+                split,
+                ratio,
+                first,
+                second,
+            } => {
+                let (first_area, second_area) = match split {
+                    Split::Vertical => split_width(area, *ratio, gaps),
+                    Split::Horizontal => split_height(area, *ratio, gaps),
+                };
+                first.geometry(first_area, gaps, out);
+                second.geometry(second_area, gaps, out);
+            }
+        }
+    }
+}
+
+impl BspTree {
+    pub fn contains(&self, window: Window) -> bool {
+        self.root.as_ref().is_some_and(|n| n.contains(window))
+    }
+
+    pub fn windows(&self) -> Vec<Window> {
+        let mut out = Vec::new();
+        if let Some(root) = &self.root {
+            root.leaves(&mut out);
+        }
+        out
+    }
+
+    pub fn geometry(&self, area: Rect, gaps: u32) -> Vec<(Window, Rect)> {
+        let mut out = Vec::new();
+        if let Some(root) = &self.root {
+            root.geometry(area, gaps, &mut out);
+        }
+        out
+    }
+
+    // splits `target` (or the last leaf) in the preselected direction, or along
+    // its longest side when nothing was preselected
+    pub fn insert(&mut self, target: Option<Window>, window: Window, area: Rect, gaps: u32) {
+        let Some(root) = self.root.as_mut() else {
+            self.root = Some(Node::Leaf(window));
+            return;
+        };
+
+        let target = match target.filter(|&t| root.contains(t)) {
+            Some(t) => t,
+            None => {
+                let mut leaves = Vec::new();
+                root.leaves(&mut leaves);
+                leaves[leaves.len() - 1]
+            }
+        };
+
+        let dir = self.presel.take().unwrap_or_else(|| {
+            let mut geoms = Vec::new();
+            root.geometry(area, gaps, &mut geoms);
+            match geoms.iter().find(|g| g.0 == target) {
+                Some((_, r)) if r.h > r.w => Direction::Down,
+                _ => Direction::Right,
+            }
+        });
+
+        root.insert(target, window, dir);
+    }
+
+    pub fn remove(&mut self, window: Window) {
+        self.root = self.root.take().and_then(|n| n.remove(window));
+    }
+
+    fn node_for(&mut self, window: Option<Window>) -> Option<&mut Node> {
+        let root = self.root.as_mut()?;
+        match window {
+            Some(w) if root.contains(w) && !root.is_leaf(w) => root.parent_of(w),
+            _ => Some(root),
+        }
+    }
+
+    pub fn rotate(&mut self, focused: Option<Window>, clockwise: bool) {
+        if let Some(node) = self.node_for(focused) {
+            node.rotate(clockwise);
+        }
+    }
+
+    pub fn flip(&mut self, focused: Option<Window>, axis: Split) {
+        if let Some(node) = self.node_for(focused) {
+            node.flip(axis);
+        }
+    }
+
+    pub fn balance(&mut self) {
+        if let Some(root) = self.root.as_mut() {
+            root.balance();
+        }
+    }
+
+    // grows (or shrinks) the focused window inside its parent split
+    pub fn resize(&mut self, focused: Window, delta: f32) {
+        let Some(root) = self.root.as_mut() else {
+            return;
+        };
+
+        if let Some(Node::Split { ratio, first, .. }) = root.parent_of(focused) {
+            let delta = if first.contains(focused) {
+                delta
+            } else {
+                -delta
+            };
+            *ratio = (*ratio + delta).clamp(0.10, 0.90);
+        }
+    }
+}
+
+// manual tiling, geometry comes from the monitor's split tree instead of list order
+pub struct Bsp;
+
+impl Layout for Bsp {
+    fn name(&self) -> &'static str {
+        "bsp"
+    }
+
+    fn symbol(&self) -> &'static str {
+        "[+]"
+    }
+
+    fn arrange(
+        &self,
+        area: Rect,
+        windows: &[Window],
+        params: &mut LayoutParams,
+    ) -> Vec<(Window, Rect)> {
+        let tree = &mut *params.tree;
+
+        for window in tree.windows() {
+            if !windows.contains(&window) {
+                tree.remove(window);
+            }
+        }
+        for &window in windows.iter() {
+            if !tree.contains(window) {
+                tree.insert(params.focused, window, area, params.gaps);
+            }
+        }
+
+        tree.geometry(area, params.gaps)
+    }
+}
+
+impl KoanWM {
+    // every desktop keeps its own tree, the hidden ones stay untouched
+    pub fn tree_key(&self, mon_idx: usize) -> (usize, usize) {
+        (mon_idx, self.monitors.get(mon_idx).map_or(0, |m| m.desktop))
+    }
+
+    pub fn presel(&mut self, dir: Direction) {
+        let key = self.tree_key(self.current_monitor);
+        self.trees.entry(key).or_default().presel = Some(dir);
+    }
+
+    pub fn rotate_tree(&mut self) {
+        let key = self.tree_key(self.current_monitor);
+        if let Some(tree) = self.trees.get_mut(&key) {
+            tree.rotate(self.focused, true);
+        }
+        if self.current_layout().name() == Bsp.name() {
+            let _ = self.layout();
+        }
+    }
+
+    pub fn flip_tree(&mut self, axis: Split) {
+        let key = self.tree_key(self.current_monitor);
+        if let Some(tree) = self.trees.get_mut(&key) {
+            tree.flip(self.focused, axis);
+        }
+        if self.current_layout().name() == Bsp.name() {
+            let _ = self.layout();
+        }
+    }
+
+    pub fn balance_tree(&mut self) {
+        let key = self.tree_key(self.current_monitor);
+        if let Some(tree) = self.trees.get_mut(&key) {
+            tree.balance();
+        }
+        if self.current_layout().name() == Bsp.name() {
+            let _ = self.layout();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AREA: Rect = Rect {
+        x: 0,
+        y: 0,
+        w: 1000,
+        h: 800,
+    };
+
+    fn tree(windows: &[Window]) -> BspTree {
+        let mut tree = BspTree::default();
+        for &w in windows {
+            tree.insert(None, w, AREA, 0);
+        }
+        tree
+    }
+
+    fn rect_of(tree: &BspTree, window: Window) -> Rect {
+        tree.geometry(AREA, 0)
+            .into_iter()
+            .find(|(w, _)| *w == window)
+            .unwrap()
+            .1
+    }
+
+    #[test]
+    fn splits_the_longest_side_by_default() {
+        let t = tree(&[1, 2]);
+        assert_eq!(rect_of(&t, 1).w, 500);
+        assert_eq!(rect_of(&t, 2).x, 500);
+
+        // 500x800 now, the next split goes down
+        let t = tree(&[1, 2, 3]);
+        assert_eq!(rect_of(&t, 3).y, 400);
+    }
+
+    #[test]
+    fn preselection_is_used_once() {
+        let mut t = tree(&[1]);
+        t.presel = Some(Direction::Up);
+        t.insert(Some(1), 2, AREA, 0);
+        assert_eq!(rect_of(&t, 2).y, 0);
+        assert_eq!(rect_of(&t, 1).y, 400);
+        assert!(t.presel.is_none());
+    }
+
+    #[test]
+    fn removing_a_leaf_collapses_its_parent() {
+        let mut t = tree(&[1, 2, 3]);
+        t.remove(2);
+        assert_eq!(t.windows(), vec![1, 3]);
+        assert_eq!(rect_of(&t, 3).w, 500);
+        t.remove(1);
+        t.remove(3);
+        assert!(t.root.is_none());
+    }
+
+    #[test]
+    fn rotate_flip_and_balance() {
+        let mut t = tree(&[1, 2]);
+        t.rotate(None, true);
+        assert_eq!(rect_of(&t, 1).y, 0);
+        assert_eq!(rect_of(&t, 2).y, 400);
+
+        t.flip(None, Split::Horizontal);
+        assert_eq!(rect_of(&t, 2).y, 0);
+
+        t.resize(2, 0.2);
+        assert_eq!(rect_of(&t, 2).h, 560);
+        t.balance();
+        assert_eq!(rect_of(&t, 2).h, 400);
+    }
+}
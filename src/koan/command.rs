@@ -0,0 +1,231 @@
+use std::{
+    io::{BufRead, BufReader},
+    process::{Command, Stdio},
+    sync::mpsc::{self, Sender},
+    thread,
+    time::Duration,
+};
+
+use super::{backend::Waker, modeline::ModuleType, sysinfo, KoanWM};
+
+// what a module updated off the event loop shows, `alert` draws it in
+// Color::Error
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Reading {
+    pub text: String,
+    pub alert: bool,
+}
+
+impl Reading {
+    pub fn plain(text: String) -> Self {
+        Self { text, alert: false }
+    }
+}
+
+// module index, new reading
+pub type Update = (usize, Reading);
+
+impl KoanWM {
+    // commands and system info modules each get a thread, a slow command or
+    // file never holds up the event loop
+    pub fn spawn_background_modules(&mut self) {
+        let (sender, receiver) = mpsc::channel();
+
+        for (idx, module) in self.modules.iter().enumerate() {
+            let publisher = Publisher::new(idx, sender.clone(), self.backend.waker());
+            match module.kind {
+                ModuleType::Command {
+                    command,
+                    interval: Some(secs),
+                } => every(secs, publisher, move || Reading::plain(run(command))),
+                ModuleType::Command {
+                    command,
+                    interval: None,
+                } => {
+                    thread::spawn(move || follow(command, publisher));
+                }
+                ref kind => {
+                    if let Some((secs, sample)) = sysinfo::sampler(kind) {
+                        every(secs, publisher, sample);
+                    }
+                }
+            }
+        }
+        self.module_updates = Some(receiver);
+    }
+
+    // caches the readings, redrawing only when something changed
+    pub fn handle_module_output(&mut self) {
+        let Some(updates) = &self.module_updates else {
+            return;
+        };
+
+        let mut changed = false;
+        for (idx, reading) in updates.try_iter() {
+            changed |= self.module_readings.insert(idx, reading.clone()) != Some(reading);
+        }
+        if changed {
+            self.update_modelines();
+        }
+    }
+}
+
+// sends the readings of one module that differ from the previous one
+pub struct Publisher {
+    idx: usize,
+    last: Option<Reading>,
+    updates: Sender<Update>,
+    wake: Waker,
+}
+
+impl Publisher {
+    pub fn new(idx: usize, updates: Sender<Update>, wake: Waker) -> Self {
+        Self {
+            idx,
+            last: None,
+            updates,
+            wake,
+        }
+    }
+
+    // false once koan is gone and the thread should stop
+    fn publish(&mut self, reading: Reading) -> bool {
+        if self.last.as_ref() == Some(&reading) {
+            return true;
+        }
+        self.last = Some(reading.clone());
+        self.updates.send((self.idx, reading)).is_ok() && (self.wake)()
+    }
+}
+
+// an interval of 0 would fork commands like amixer in a tight loop, a second
+// is the shortest one
+pub fn every(
+    secs: u64,
+    mut publisher: Publisher,
+    mut sample: impl FnMut() -> Reading + Send + 'static,
+) {
+    let interval = Duration::from_secs(secs.max(1));
+    thread::spawn(move || {
+        while publisher.publish(sample()) {
+            thread::sleep(interval);
+        }
+    });
+}
+
+fn shell(command: &str) -> Command {
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c")
+        .arg(command)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null());
+    cmd
+}
+
+// first line of the output
+pub fn run(command: &str) -> String {
+    match shell(command).output() {
+        Ok(output) => String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .next()
+            .unwrap_or("")
+            .trim_end()
+            .to_string(),
+        Err(e) => {
+            eprintln!("failed to run {}: {}", command, e);
+            String::new()
+        }
+    }
+}
+
+// shows each line a long-running command prints
+fn follow(command: &str, mut publisher: Publisher) {
+    let mut child = match shell(command).stdout(Stdio::piped()).spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            eprintln!("failed to run {}: {}", command, e);
+            return;
+        }
+    };
+    let Some(stdout) = child.stdout.take() else {
+        return;
+    };
+
+    for line in BufReader::new(stdout).split(b'\n') {
+        let Ok(line) = line else { break };
+        let text = String::from_utf8_lossy(&line).trim_end().to_string();
+        if !publisher.publish(Reading::plain(text)) {
+            break;
+        }
+    }
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::koan::{
+        backend::{
+            fake::{Fake, SCREEN},
+            Event,
+        },
+        modeline::{Alignment, Module},
+    };
+    use std::time::Instant;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    fn publisher(sender: Sender<Update>) -> Publisher {
+        Publisher::new(0, sender, Box::new(|| true))
+    }
+
+    #[test]
+    fn periodic_commands_show_their_first_line() {
+        let (sender, receiver) = mpsc::channel();
+        every(60, publisher(sender), || {
+            Reading::plain(run("printf 'a \\nb'"))
+        });
+
+        let (_, reading) = receiver.recv_timeout(TIMEOUT).unwrap();
+        assert_eq!(reading.text, "a");
+    }
+
+    #[test]
+    fn long_running_commands_send_lines_that_change() {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || follow("echo a; echo a; echo b", publisher(sender)));
+
+        let texts: Vec<String> = receiver.iter().map(|(_, r)| r.text).collect();
+        assert_eq!(texts, ["a", "b"]);
+    }
+
+    #[test]
+    fn command_output_reaches_the_modeline() {
+        static MODULES: &[Module] = &[Module {
+            kind: ModuleType::Command {
+                command: "echo from-the-shell",
+                interval: None,
+            },
+            alignment: Alignment::Right,
+            fg: None,
+            bg: None,
+            clicks: &[],
+        }];
+        let fake = Fake::new(&[SCREEN]);
+        let mut wm = fake.koan();
+        wm.modules = MODULES;
+        wm.create_modelines();
+        wm.spawn_background_modules();
+
+        let start = Instant::now();
+        while wm.module_readings.is_empty() && start.elapsed() < TIMEOUT {
+            thread::sleep(Duration::from_millis(10));
+            fake.push(Event::ModuleOutput);
+            wm.run().unwrap();
+        }
+
+        let texts = fake.with_window(wm.modelines[&0], |w| w.texts.clone());
+        assert!(texts.iter().any(|(_, t)| t == "from-the-shell"));
+    }
+}
@@ -1,13 +1,22 @@
 use x11::keysym::{
-    XK_Return, XK_Tab, XK_c, XK_comma, XK_d, XK_h, XK_j, XK_k, XK_l, XK_period, XK_q, XK_space,
+    XK_Down, XK_Left, XK_Return, XK_Right, XK_Tab, XK_Up, XK_b, XK_c, XK_comma, XK_d, XK_equal,
+    XK_f, XK_h, XK_i, XK_j, XK_k, XK_l, XK_period, XK_q, XK_r, XK_space, XK_1, XK_2, XK_3, XK_4,
+    XK_5, XK_6, XK_7, XK_8, XK_9,
+};
+use x11rb::protocol::xproto::{ButtonIndex, ModMask};
+
+use super::{
+    layouts::Rect,
+    modeline::{Alignment, ClickAction, Module, ModuleClick, ModuleType},
+    window::Direction,
 };
-use x11::xlib::{ControlMask, Mod1Mask, ShiftMask};
 
 #[allow(dead_code)]
 pub enum Color {
     Primary,
     Secondary,
     Error,
+    Inactive,
 }
 
 impl Color {
@@ -16,6 +25,7 @@ impl Color {
             Color::Primary => "#00ff00",
             Color::Secondary => "#000000",
             Color::Error => "#ff5555",
+            Color::Inactive => "#555555",
         }
     }
 }
@@ -42,142 +52,582 @@ pub enum Action {
     DecreaseWidth,
     MoveWindowToNextMonitor,
     MoveWindowToPrevMonitor,
+    MonitorLeft,
+    MonitorRight,
+    MonitorUp,
+    MonitorDown,
+    MoveWindowToMonitorLeft,
+    MoveWindowToMonitorRight,
+    MoveWindowToMonitorUp,
+    MoveWindowToMonitorDown,
     ToggleFloat,
     CloseWindow,
+    PreselUp,
+    PreselDown,
+    PreselLeft,
+    PreselRight,
+    RotateTree,
+    FlipHorizontal,
+    FlipVertical,
+    BalanceTree,
+    IncMaster,
+    DecMaster,
+    ViewDesktop(usize),
+    MoveToDesktop(usize),
+    ToggleModeline,
 }
 
 #[derive(Debug)]
 pub struct KeyBinding {
     pub keysym: u32,
-    pub modifiers: u32,
+    pub modifiers: &'static [ModMask], // all of them held
     pub action: Action,
 }
 
-const MOD_KEY: u32 = Mod1Mask;
+impl KeyBinding {
+    pub fn mask(&self) -> ModMask {
+        self.modifiers
+            .iter()
+            .fold(ModMask::from(0u16), |mask, &m| mask | m)
+    }
+}
+
+const MOD_KEY: ModMask = ModMask::M1;
 
 pub const KEY_BINDINGS: &[KeyBinding] = &[
     KeyBinding {
         keysym: XK_Tab,
-        modifiers: (MOD_KEY | ControlMask) as u32,
+        modifiers: &[MOD_KEY, ModMask::CONTROL],
         action: Action::SwitchLayout,
     },
     KeyBinding {
         keysym: XK_Return,
-        modifiers: MOD_KEY as u32,
+        modifiers: &[MOD_KEY],
         action: Action::OpenTerminal,
     },
     KeyBinding {
         keysym: XK_d,
-        modifiers: MOD_KEY as u32,
+        modifiers: &[MOD_KEY],
         action: Action::OpenLauncher,
     },
     // Focus - MOD + hjkl (navegación)
     KeyBinding {
         keysym: XK_k,
-        modifiers: MOD_KEY as u32,
+        modifiers: &[MOD_KEY],
         action: Action::FocusUp,
     },
     KeyBinding {
         keysym: XK_j,
-        modifiers: MOD_KEY as u32,
+        modifiers: &[MOD_KEY],
         action: Action::FocusDown,
     },
     KeyBinding {
         keysym: XK_l,
-        modifiers: MOD_KEY as u32,
+        modifiers: &[MOD_KEY],
         action: Action::FocusRight,
     },
     KeyBinding {
         keysym: XK_h,
-        modifiers: MOD_KEY as u32,
+        modifiers: &[MOD_KEY],
         action: Action::FocusLeft,
     },
     // Move/Swap - MOD + Shift + hjkl (mover float o swap en layout)
     KeyBinding {
         keysym: XK_k,
-        modifiers: (MOD_KEY | ShiftMask) as u32,
+        modifiers: &[MOD_KEY, ModMask::SHIFT],
         action: Action::MoveUp,
     },
     KeyBinding {
         keysym: XK_j,
-        modifiers: (MOD_KEY | ShiftMask) as u32,
+        modifiers: &[MOD_KEY, ModMask::SHIFT],
         action: Action::MoveDown,
     },
     KeyBinding {
         keysym: XK_l,
-        modifiers: (MOD_KEY | ShiftMask) as u32,
+        modifiers: &[MOD_KEY, ModMask::SHIFT],
         action: Action::MoveRight,
     },
     KeyBinding {
         keysym: XK_h,
-        modifiers: (MOD_KEY | ShiftMask) as u32,
+        modifiers: &[MOD_KEY, ModMask::SHIFT],
         action: Action::MoveLeft,
     },
     // Resize - MOD + Control + hjkl
     KeyBinding {
         keysym: XK_j,
-        modifiers: (MOD_KEY | ControlMask) as u32,
+        modifiers: &[MOD_KEY, ModMask::CONTROL],
         action: Action::IncreaseHeight,
     },
     KeyBinding {
         keysym: XK_k,
-        modifiers: (MOD_KEY | ControlMask) as u32,
+        modifiers: &[MOD_KEY, ModMask::CONTROL],
         action: Action::DecreaseHeight,
     },
     KeyBinding {
         keysym: XK_h,
-        modifiers: (MOD_KEY | ControlMask) as u32,
+        modifiers: &[MOD_KEY, ModMask::CONTROL],
         action: Action::DecreaseWidth,
     },
     KeyBinding {
         keysym: XK_l,
-        modifiers: (MOD_KEY | ControlMask) as u32,
+        modifiers: &[MOD_KEY, ModMask::CONTROL],
         action: Action::IncreaseWidth,
     },
+    // Master count - MOD + i / MOD + Shift + i
+    KeyBinding {
+        keysym: XK_i,
+        modifiers: &[MOD_KEY],
+        action: Action::IncMaster,
+    },
+    KeyBinding {
+        keysym: XK_i,
+        modifiers: &[MOD_KEY, ModMask::SHIFT],
+        action: Action::DecMaster,
+    },
     // Monitor navigation - MOD + comma/period
     KeyBinding {
         keysym: XK_comma,
-        modifiers: MOD_KEY as u32,
+        modifiers: &[MOD_KEY],
         action: Action::MonitorPrev,
     },
     KeyBinding {
         keysym: XK_period,
-        modifiers: MOD_KEY as u32,
+        modifiers: &[MOD_KEY],
         action: Action::MonitorNext,
     },
     // Move window to monitor - MOD + Shift + comma/period
     KeyBinding {
         keysym: XK_comma,
-        modifiers: (MOD_KEY | ShiftMask) as u32,
+        modifiers: &[MOD_KEY, ModMask::SHIFT],
         action: Action::MoveWindowToPrevMonitor,
     },
     KeyBinding {
         keysym: XK_period,
-        modifiers: (MOD_KEY | ShiftMask) as u32,
+        modifiers: &[MOD_KEY, ModMask::SHIFT],
         action: Action::MoveWindowToNextMonitor,
     },
+    // Monitor by position - MOD + arrows, MOD + Shift + arrows moves the window
+    KeyBinding {
+        keysym: XK_Left,
+        modifiers: &[MOD_KEY],
+        action: Action::MonitorLeft,
+    },
+    KeyBinding {
+        keysym: XK_Right,
+        modifiers: &[MOD_KEY],
+        action: Action::MonitorRight,
+    },
+    KeyBinding {
+        keysym: XK_Up,
+        modifiers: &[MOD_KEY],
+        action: Action::MonitorUp,
+    },
+    KeyBinding {
+        keysym: XK_Down,
+        modifiers: &[MOD_KEY],
+        action: Action::MonitorDown,
+    },
+    KeyBinding {
+        keysym: XK_Left,
+        modifiers: &[MOD_KEY, ModMask::SHIFT],
+        action: Action::MoveWindowToMonitorLeft,
+    },
+    KeyBinding {
+        keysym: XK_Right,
+        modifiers: &[MOD_KEY, ModMask::SHIFT],
+        action: Action::MoveWindowToMonitorRight,
+    },
+    KeyBinding {
+        keysym: XK_Up,
+        modifiers: &[MOD_KEY, ModMask::SHIFT],
+        action: Action::MoveWindowToMonitorUp,
+    },
+    KeyBinding {
+        keysym: XK_Down,
+        modifiers: &[MOD_KEY, ModMask::SHIFT],
+        action: Action::MoveWindowToMonitorDown,
+    },
     KeyBinding {
         keysym: XK_c,
-        modifiers: (MOD_KEY | ShiftMask) as u32,
+        modifiers: &[MOD_KEY, ModMask::SHIFT],
         action: Action::CloseWindow,
     },
     KeyBinding {
         keysym: XK_space,
-        modifiers: MOD_KEY as u32,
+        modifiers: &[MOD_KEY],
         action: Action::ToggleFloat,
     },
+    // BSP - MOD + Control + Shift + hjkl (preselect split direction)
+    KeyBinding {
+        keysym: XK_k,
+        modifiers: &[MOD_KEY, ModMask::CONTROL, ModMask::SHIFT],
+        action: Action::PreselUp,
+    },
+    KeyBinding {
+        keysym: XK_j,
+        modifiers: &[MOD_KEY, ModMask::CONTROL, ModMask::SHIFT],
+        action: Action::PreselDown,
+    },
+    KeyBinding {
+        keysym: XK_h,
+        modifiers: &[MOD_KEY, ModMask::CONTROL, ModMask::SHIFT],
+        action: Action::PreselLeft,
+    },
+    KeyBinding {
+        keysym: XK_l,
+        modifiers: &[MOD_KEY, ModMask::CONTROL, ModMask::SHIFT],
+        action: Action::PreselRight,
+    },
+    KeyBinding {
+        keysym: XK_r,
+        modifiers: &[MOD_KEY],
+        action: Action::RotateTree,
+    },
+    KeyBinding {
+        keysym: XK_f,
+        modifiers: &[MOD_KEY],
+        action: Action::FlipHorizontal,
+    },
+    KeyBinding {
+        keysym: XK_f,
+        modifiers: &[MOD_KEY, ModMask::SHIFT],
+        action: Action::FlipVertical,
+    },
+    KeyBinding {
+        keysym: XK_equal,
+        modifiers: &[MOD_KEY],
+        action: Action::BalanceTree,
+    },
+    // Desktops - MOD + 1-9, MOD + Shift + 1-9 sends the window there
+    KeyBinding {
+        keysym: XK_1,
+        modifiers: &[MOD_KEY],
+        action: Action::ViewDesktop(0),
+    },
+    KeyBinding {
+        keysym: XK_1,
+        modifiers: &[MOD_KEY, ModMask::SHIFT],
+        action: Action::MoveToDesktop(0),
+    },
+    KeyBinding {
+        keysym: XK_2,
+        modifiers: &[MOD_KEY],
+        action: Action::ViewDesktop(1),
+    },
+    KeyBinding {
+        keysym: XK_2,
+        modifiers: &[MOD_KEY, ModMask::SHIFT],
+        action: Action::MoveToDesktop(1),
+    },
+    KeyBinding {
+        keysym: XK_3,
+        modifiers: &[MOD_KEY],
+        action: Action::ViewDesktop(2),
+    },
+    KeyBinding {
+        keysym: XK_3,
+        modifiers: &[MOD_KEY, ModMask::SHIFT],
+        action: Action::MoveToDesktop(2),
+    },
+    KeyBinding {
+        keysym: XK_4,
+        modifiers: &[MOD_KEY],
+        action: Action::ViewDesktop(3),
+    },
+    KeyBinding {
+        keysym: XK_4,
+        modifiers: &[MOD_KEY, ModMask::SHIFT],
+        action: Action::MoveToDesktop(3),
+    },
+    KeyBinding {
+        keysym: XK_5,
+        modifiers: &[MOD_KEY],
+        action: Action::ViewDesktop(4),
+    },
+    KeyBinding {
+        keysym: XK_5,
+        modifiers: &[MOD_KEY, ModMask::SHIFT],
+        action: Action::MoveToDesktop(4),
+    },
+    KeyBinding {
+        keysym: XK_6,
+        modifiers: &[MOD_KEY],
+        action: Action::ViewDesktop(5),
+    },
+    KeyBinding {
+        keysym: XK_6,
+        modifiers: &[MOD_KEY, ModMask::SHIFT],
+        action: Action::MoveToDesktop(5),
+    },
+    KeyBinding {
+        keysym: XK_7,
+        modifiers: &[MOD_KEY],
+        action: Action::ViewDesktop(6),
+    },
+    KeyBinding {
+        keysym: XK_7,
+        modifiers: &[MOD_KEY, ModMask::SHIFT],
+        action: Action::MoveToDesktop(6),
+    },
+    KeyBinding {
+        keysym: XK_8,
+        modifiers: &[MOD_KEY],
+        action: Action::ViewDesktop(7),
+    },
+    KeyBinding {
+        keysym: XK_8,
+        modifiers: &[MOD_KEY, ModMask::SHIFT],
+        action: Action::MoveToDesktop(7),
+    },
+    KeyBinding {
+        keysym: XK_9,
+        modifiers: &[MOD_KEY],
+        action: Action::ViewDesktop(8),
+    },
+    KeyBinding {
+        keysym: XK_9,
+        modifiers: &[MOD_KEY, ModMask::SHIFT],
+        action: Action::MoveToDesktop(8),
+    },
+    KeyBinding {
+        keysym: XK_b,
+        modifiers: &[MOD_KEY],
+        action: Action::ToggleModeline,
+    },
     KeyBinding {
         keysym: XK_q,
-        modifiers: (MOD_KEY | ShiftMask) as u32,
+        modifiers: &[MOD_KEY, ModMask::SHIFT],
         action: Action::QuitWM,
     },
 ];
 
+// Xft font names, the first one sets the bar text height and the others are
+// tried in order for the characters it does not have
+pub const FONTS: &[&str] = &[
+    "monospace:size=9",
+    "Noto Color Emoji:size=9",
+    "Noto Sans CJK JP:size=9",
+];
+
+// every monitor has its own set, named by _NET_DESKTOP_NAMES when a tool sets it
+pub const DESKTOPS: &[&str] = &["1", "2", "3", "4", "5", "6", "7", "8", "9"];
+
+// modeline content, each side drawn in this order. `fg` and `bg` replace the
+// modeline colors of a module, status2d and alert colors still win. Command
+// modules take shell commands, for example
+// Module {
+//     kind: ModuleType::Command {
+//         command: "date +%V",
+//         interval: Some(3600),
+//     },
+//     alignment: Alignment::Right,
+//     fg: Some("#000000"),
+//     bg: Some("#88c0d0"),
+//     clicks: &[],
+// },
+// and the system info ones a format with their own placeholders. Clicks run an
+// action or a program, here scrolling changes the volume
+// Module {
+//     kind: ModuleType::Volume {
+//         control: "Master",
+//         format: "vol {volume}% ",
+//         muted: "muted ",
+//         interval: 2,
+//     },
+//     alignment: Alignment::Right,
+//     fg: None,
+//     bg: None,
+//     clicks: &[
+//         ModuleClick {
+//             button: ButtonIndex::M4,
+//             action: ClickAction::Spawn(AppCommand {
+//                 program: "amixer",
+//                 args: &["-q", "set", "Master", "5%+"],
+//             }),
+//         },
+//         ModuleClick {
+//             button: ButtonIndex::M5,
+//             action: ClickAction::Spawn(AppCommand {
+//                 program: "amixer",
+//                 args: &["-q", "set", "Master", "5%-"],
+//             }),
+//         },
+//     ],
+// },
+pub const MODULES: &[Module] = &[
+    Module {
+        kind: ModuleType::Desktops,
+        alignment: Alignment::Left,
+        fg: None,
+        bg: None,
+        clicks: &[],
+    },
+    Module {
+        kind: ModuleType::Layout,
+        alignment: Alignment::Left,
+        fg: None,
+        bg: None,
+        clicks: &[
+            ModuleClick {
+                button: ButtonIndex::M1,
+                action: ClickAction::Action(Action::SwitchLayout),
+            },
+            ModuleClick {
+                button: ButtonIndex::M3,
+                action: ClickAction::Action(Action::SwitchLayout),
+            },
+        ],
+    },
+    Module {
+        kind: ModuleType::WindowTitle,
+        alignment: Alignment::Left,
+        fg: None,
+        bg: None,
+        clicks: &[
+            ModuleClick {
+                button: ButtonIndex::M1,
+                action: ClickAction::Action(Action::FocusRight),
+            },
+            ModuleClick {
+                button: ButtonIndex::M4,
+                action: ClickAction::Action(Action::FocusLeft),
+            },
+            ModuleClick {
+                button: ButtonIndex::M5,
+                action: ClickAction::Action(Action::FocusRight),
+            },
+        ],
+    },
+    Module {
+        kind: ModuleType::ClientCount,
+        alignment: Alignment::Left,
+        fg: None,
+        bg: None,
+        clicks: &[],
+    },
+    Module {
+        kind: ModuleType::RootName,
+        alignment: Alignment::Right,
+        fg: None,
+        bg: None,
+        clicks: &[],
+    },
+    Module {
+        kind: ModuleType::Cpu {
+            format: "cpu {usage}% ",
+            high: 90,
+            interval: 2,
+        },
+        alignment: Alignment::Right,
+        fg: None,
+        bg: None,
+        clicks: &[],
+    },
+    Module {
+        kind: ModuleType::Memory {
+            format: "mem {used} ",
+            high: 90,
+            interval: 5,
+        },
+        alignment: Alignment::Right,
+        fg: None,
+        bg: None,
+        clicks: &[],
+    },
+    Module {
+        kind: ModuleType::Battery {
+            format: "bat {capacity}% ",
+            low: 15,
+            interval: 30,
+        },
+        alignment: Alignment::Right,
+        fg: None,
+        bg: None,
+        clicks: &[],
+    },
+    Module {
+        kind: ModuleType::Clock,
+        alignment: Alignment::Right,
+        fg: None,
+        bg: None,
+        clicks: &[ModuleClick {
+            button: ButtonIndex::M1,
+            action: ClickAction::Spawn(AppCommand {
+                program: "sh",
+                args: &["-c", "notify-send \"$(date +%B)\" \"$(cal)\""],
+            }),
+        }],
+    },
+];
+
+// icons of nm-applet and the like, at the right of the primary monitor's modeline
+pub const SYSTEM_TRAY: bool = true;
 pub const CURSOR_ENTER_FOCUS: bool = false;
 pub const MODELINE_UPDATE_TIME: u64 = 5;
 pub const MODELINE_HEIGHT: u32 = 15;
+// space before the first and after the last module
+pub const MODELINE_PADDING: i32 = 10;
+// drawn between the modules of each side that show something, " | " for example
+pub const MODELINE_SEPARATOR: &str = "";
+pub const TAB_BAR_HEIGHT: u32 = 15;
 pub const BORDER_WIDTH: u32 = 1;
 pub const GAPS: u32 = 4;
+pub const NMASTER: usize = 1;
+pub const MFACT: f32 = 0.5;
+pub const DEFAULT_LAYOUT: &str = "monocle";
+// order used by SwitchLayout, names come from layouts::REGISTRY
+pub const LAYOUTS: &[&str] = &[
+    "monocle",
+    "tile",
+    "dwindle",
+    "spiral",
+    "bsp",
+    "centeredmaster",
+    "threecol",
+    "tabbed",
+    "deck",
+];
+pub const MASTER_ORIENTATION: Direction = Direction::Left; // side of the master area
+
+// fake monitors carved out of the screen, used instead of the RandR outputs
+// when not empty. KOAN_MONITORS="800x600+0+0,800x600+800+0" overrides it
+pub const VIRTUAL_MONITORS: &[Rect] = &[];
+// monitor next/prev and the arrows go around from the last monitor to the first
+pub const MONITOR_WRAP: bool = false;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ModelinePosition {
+    Top,
+    #[allow(dead_code)] // only chosen in OUTPUTS, which ships empty
+    Bottom,
+}
+
+pub struct OutputConfig {
+    pub name: &'static str, // RandR output name, see `xrandr --listmonitors`
+    pub layout: &'static str,
+    pub gaps: u32,
+    pub modeline: Option<ModelinePosition>, // None hides it
+    pub apps: &'static [&'static str],      // WM_CLASS instance or class opened here
+}
+
+// used by the outputs missing from OUTPUTS
+pub const DEFAULT_OUTPUT: OutputConfig = OutputConfig {
+    name: "",
+    layout: DEFAULT_LAYOUT,
+    gaps: GAPS,
+    modeline: Some(ModelinePosition::Top),
+    apps: &[],
+};
+
+pub const OUTPUTS: &[OutputConfig] = &[
+    // OutputConfig {
+    //     name: "HDMI-1",
+    //     layout: "tile",
+    //     modeline: Some(ModelinePosition::Bottom),
+    //     apps: &["firefox"],
+    //     ..DEFAULT_OUTPUT
+    // },
+];
 
 pub struct AppCommand {
     pub program: &'static str,
@@ -1,30 +1,24 @@
-use super::layouts::LAYOUTS;
-use super::modeline::Module;
-use super::monitors::Monitor;
+use super::backend::{xorg::Xorg, Atom, Backend, Event};
+use super::bsp::BspTree;
+use super::command::{Reading, Update};
+use super::layouts::Rect;
+use super::modeline::{Click, Module};
+use super::monitors::{parse_monitors, Monitor};
 use super::window::{Client, Window};
-use super::{config::KEY_BINDINGS, error::KoanWMError};
-
-use std::collections::HashMap;
-use std::mem::zeroed;
-use std::ptr::null;
-use x11::xlib;
-
-extern "C" fn x_error_handler(_: *mut xlib::Display, ev: *mut xlib::XErrorEvent) -> i32 {
-    let e = unsafe { *ev };
-    if e.error_code == xlib::BadWindow || (e.error_code == 8 && e.request_code == 42) {
-        eprintln!(
-            "X11 Error: code={}, request={}, minor={}",
-            e.error_code, e.request_code, e.minor_code
-        );
-        return 0;
-    }
-    0
-}
+use super::{
+    config::{
+        KeyBinding, OutputConfig, DESKTOPS, KEY_BINDINGS, MODULES, OUTPUTS, VIRTUAL_MONITORS,
+    },
+    error::KoanWMError,
+};
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::mpsc::Receiver,
+};
 
 pub struct KoanWM {
-    pub mfact: f32,
-    pub layout: LAYOUTS,
-    pub display: *mut xlib::Display,
+    pub backend: Box<dyn Backend>,
 
     pub monitors: Vec<Monitor>,
     pub current_monitor: usize,
@@ -32,147 +26,254 @@ pub struct KoanWM {
     pub clients: Vec<Client>,
     pub focused: Option<Window>,
     pub window_monitors: HashMap<Window, usize>, // window, monitor_idx
+    pub window_homes: HashMap<Window, String>,   // window, disconnected monitor name
+    pub trees: HashMap<(usize, usize), BspTree>, // (monitor_idx, desktop), split tree
+    pub virtual_monitors: Vec<Rect>,
+    pub output_configs: &'static [OutputConfig],
+    pub key_bindings: &'static [KeyBinding],
 
-    pub gc: xlib::GC, // Contexto gráfico para dibujar
-    pub modelines: Vec<Window>,
-    pub modules: Vec<Module>,
+    pub window_desktops: HashMap<Window, usize>, // window, desktop index
+    pub desktop_names: Vec<String>,
+    pub hidden: HashSet<Window>, // unmapped because their desktop is not shown
+    pub ignored_unmaps: HashMap<Window, usize>, // window, unmaps caused by koan
+
+    pub modelines: HashMap<usize, Window>, // monitor_idx, modeline
+    pub modules: &'static [Module],
+    pub module_readings: HashMap<usize, Reading>, // module index, last reading
+    pub module_updates: Option<Receiver<Update>>,
+    // modeline, x ranges
+    pub modeline_clicks: HashMap<Window, Vec<(i32, i32, Click)>>,
+    pub tab_bars: HashMap<usize, Window>, // monitor_idx, tab bar
+    pub tray: Option<Window>,
+    pub tray_icons: Vec<Window>,
+    pub hidden_tray_icons: HashSet<Window>, // unmapped, by koan or never mapped
 
     // atoms
-    pub wm_protocols: xlib::Atom,
-    pub wm_delete: xlib::Atom,
-    pub wm_state: xlib::Atom,
-    pub wm_state_above: xlib::Atom,
+    pub wm_delete: Atom,
+    pub wm_state: Atom,
+    pub wm_state_above: Atom,
+    pub wm_name: Atom,
+    pub net_wm_name: Atom,
+    pub wm_hints: Atom,
+    pub net_number_of_desktops: Atom,
+    pub net_current_desktop: Atom,
+    pub net_desktop_names: Atom,
+    pub net_wm_desktop: Atom,
+    pub xembed_info: Atom,
 }
 
 impl KoanWM {
     pub fn new() -> Result<Self, KoanWMError> {
-        unsafe {
-            let display = xlib::XOpenDisplay(null());
-            if display.is_null() {
-                return Err(KoanWMError::DisplayNotFound);
+        let mut wm = Self::with_backend(Box::new(Xorg::connect()?));
+        if let Ok(spec) = std::env::var("KOAN_MONITORS") {
+            match parse_monitors(&spec) {
+                Some(monitors) => wm.virtual_monitors = monitors,
+                None => eprintln!("Ignoring invalid KOAN_MONITORS: {}", spec),
             }
+        }
+        Ok(wm)
+    }
+
+    pub fn with_backend(backend: Box<dyn Backend>) -> Self {
+        let wm_delete = backend.intern_atom("WM_DELETE_WINDOW");
+        let wm_state = backend.intern_atom("_NET_WM_STATE");
+        let wm_state_above = backend.intern_atom("_NET_WM_STATE_ABOVE");
+        let wm_name = backend.intern_atom("WM_NAME");
+        let net_wm_name = backend.intern_atom("_NET_WM_NAME");
+        let wm_hints = backend.intern_atom("WM_HINTS");
+        let net_number_of_desktops = backend.intern_atom("_NET_NUMBER_OF_DESKTOPS");
+        let net_current_desktop = backend.intern_atom("_NET_CURRENT_DESKTOP");
+        let net_desktop_names = backend.intern_atom("_NET_DESKTOP_NAMES");
+        let net_wm_desktop = backend.intern_atom("_NET_WM_DESKTOP");
+        let xembed_info = backend.intern_atom("_XEMBED_INFO");
 
-            xlib::XSetErrorHandler(Some(x_error_handler));
-
-            let wm_protocols = xlib::XInternAtom(display, "WM_PROTOCOLS\0".as_ptr() as *const _, 0);
-            let wm_delete =
-                xlib::XInternAtom(display, "WM_DELETE_WINDOW\0".as_ptr() as *const _, 0);
-            let wm_state = xlib::XInternAtom(display, "_NET_WM_STATE\0".as_ptr() as *const _, 0);
-            let wm_state_above =
-                xlib::XInternAtom(display, "_NET_WM_STATE_ABOVE\0".as_ptr() as *const _, 0);
-
-            Ok(Self {
-                layout: LAYOUTS::MONOCLE,
-                mfact: 0.5,
-                display,
-                clients: Vec::new(),
-                window_monitors: HashMap::new(),
-                focused: None,
-                monitors: Vec::new(),
-                current_monitor: 0,
-                gc: std::ptr::null_mut(),
-                modelines: Vec::new(),
-                modules: Vec::new(),
-                wm_protocols,
-                wm_delete,
-                wm_state,
-                wm_state_above,
-            })
+        Self {
+            backend,
+            clients: Vec::new(),
+            window_monitors: HashMap::new(),
+            window_homes: HashMap::new(),
+            trees: HashMap::new(),
+            virtual_monitors: VIRTUAL_MONITORS.to_vec(),
+            output_configs: OUTPUTS,
+            key_bindings: KEY_BINDINGS,
+            window_desktops: HashMap::new(),
+            desktop_names: DESKTOPS.iter().map(|d| d.to_string()).collect(),
+            hidden: HashSet::new(),
+            ignored_unmaps: HashMap::new(),
+            focused: None,
+            monitors: Vec::new(),
+            current_monitor: 0,
+            modelines: HashMap::new(),
+            modules: MODULES,
+            module_readings: HashMap::new(),
+            module_updates: None,
+            modeline_clicks: HashMap::new(),
+            tab_bars: HashMap::new(),
+            tray: None,
+            tray_icons: Vec::new(),
+            hidden_tray_icons: HashSet::new(),
+            wm_delete,
+            wm_state,
+            wm_state_above,
+            wm_name,
+            net_wm_name,
+            wm_hints,
+            net_number_of_desktops,
+            net_current_desktop,
+            net_desktop_names,
+            net_wm_desktop,
+            xembed_info,
         }
     }
 
     pub fn init(&mut self) -> Result<(), KoanWMError> {
-        unsafe {
-            let root = xlib::XDefaultRootWindow(self.display);
-            xlib::XSelectInput(
-                self.display,
-                root,
-                xlib::SubstructureRedirectMask
-                    | xlib::SubstructureNotifyMask
-                    | xlib::FocusChangeMask
-                    | xlib::EnterWindowMask
-                    | xlib::StructureNotifyMask,
-            );
-
-            self.update_monitors();
-            self.exec_autostart();
-            self.create_modelines();
-            self.spawn_modeline_timer();
-
-            xlib::XUngrabKey(self.display, xlib::AnyKey, xlib::AnyModifier, root);
-
-            for binding in KEY_BINDINGS {
-                let keycode = xlib::XKeysymToKeycode(self.display, binding.keysym as u64);
-                let status = xlib::XGrabKey(
-                    self.display,
-                    keycode as i32,
-                    binding.modifiers,
-                    root,
-                    0,
-                    xlib::GrabModeAsync,
-                    xlib::GrabModeAsync,
-                );
-                if status == 0 {
-                    eprintln!("Warning: XGrabKey failed for keysym {}", binding.keysym);
-                }
-            }
-        }
+        self.backend.select_root_input();
+
+        self.update_monitors();
+        self.init_desktops();
+        self.exec_autostart();
+        self.create_modelines();
+        self.init_tray();
+        self.spawn_modeline_timer();
+        self.spawn_background_modules();
+
+        self.backend.grab_keys(self.key_bindings);
         Ok(())
     }
 
     pub fn run(&mut self) -> Result<(), KoanWMError> {
-        let mut event: xlib::XEvent = unsafe { zeroed() };
-        loop {
-            unsafe {
-                xlib::XNextEvent(self.display, &mut event);
-                match event.get_type() {
-                    xlib::ClientMessage => {
-                        let ev = xlib::XClientMessageEvent::from(event);
-                        let update_atom = xlib::XInternAtom(
-                            self.display,
-                            "MINIWM_UPDATE_BAR\0".as_ptr() as *const _,
-                            0,
-                        );
-
-                        if ev.message_type == update_atom {
-                            self.update_modelines();
-                        }
-                    }
-                    xlib::Expose => {
-                        let ev = xlib::XExposeEvent::from(event);
-                        if self.modelines.contains(&ev.window) {
-                            self.update_modelines();
-                        }
-                    }
-                    xlib::ConfigureNotify => {
-                        let ev = xlib::XConfigureEvent::from(event);
-                        let root = xlib::XDefaultRootWindow(self.display);
-                        if ev.window == root {
-                            self.create_modelines();
-                            self.update_monitors();
-                            self.layout()?;
-                        }
-                    }
-                    xlib::MapRequest => {
-                        let ev = xlib::XMapRequestEvent::from(event);
-                        self.manage_window(ev.window)?;
+        while let Some(event) = self.backend.next_event() {
+            match event {
+                Event::ModelineTick => self.update_modelines(),
+                Event::ModuleOutput => self.handle_module_output(),
+                Event::TrayDock(icon) => self.handle_tray_dock(icon),
+                Event::Expose(window) => {
+                    if self.modelines.values().any(|&m| m == window) {
+                        self.update_modelines();
+                    } else if self.tab_bars.values().any(|&b| b == window) {
+                        self.draw_tab_bars();
                     }
-                    xlib::UnmapNotify => {
-                        let ev = xlib::XUnmapEvent::from(event);
-                        self.handle_unmap(ev.window)?;
-                    }
-                    xlib::EnterNotify => {
-                        let ev = xlib::XEnterWindowEvent::from(event);
-                        self.handle_enter_notify(ev);
+                }
+                Event::PropertyNotify { window, atom } => self.handle_property_notify(window, atom),
+                Event::ScreenChange => {
+                    self.update_monitors();
+                    self.create_modelines();
+                    self.layout()?;
+                    self.place_tray();
+                }
+                Event::MapRequest(window) => self.manage_window(window)?,
+                Event::UnmapNotify(window) => self.handle_unmap(window)?,
+                Event::DestroyNotify(window) => self.handle_destroy(window)?,
+                Event::ButtonPress { window, x, button } => {
+                    if self.handle_button_press(window, x, button).is_err() {
+                        return Ok(());
                     }
-                    xlib::KeyPress => {
-                        if let Err(_) = self.handle_keypress(xlib::XKeyEvent::from(event)) {
-                            return Ok(());
-                        }
+                }
+                Event::EnterNotify {
+                    window,
+                    x_root,
+                    y_root,
+                } => self.handle_enter_notify(window, x_root, y_root),
+                Event::KeyPress { keysym, state } => {
+                    if self.handle_keypress(keysym, state).is_err() {
+                        return Ok(());
                     }
-                    _ => {}
                 }
             }
         }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::koan::{
+        backend::fake::{Fake, SCREENS},
+        config::Action,
+    };
+    use x11::keysym::{XK_period, XK_q};
+    use x11rb::protocol::xproto::ModMask;
+
+    static BINDINGS: &[KeyBinding] = &[
+        KeyBinding {
+            keysym: XK_period,
+            modifiers: &[ModMask::M1],
+            action: Action::MoveWindowToNextMonitor,
+        },
+        KeyBinding {
+            keysym: XK_q,
+            modifiers: &[ModMask::M1],
+            action: Action::QuitWM,
+        },
+    ];
+
+    fn key(fake: &Fake, action: fn(&Action) -> bool) {
+        let binding = BINDINGS.iter().find(|b| action(&b.action)).unwrap();
+        fake.push(Event::KeyPress {
+            keysym: binding.keysym,
+            state: binding.mask(),
+        });
+    }
+
+    #[test]
+    fn scripted_session() {
+        let fake = Fake::new(&SCREENS);
+        let mut wm = fake.koan();
+        wm.key_bindings = BINDINGS;
+        wm.create_modelines();
+
+        let a = fake.create_window(300, 200);
+        let b = fake.create_window(300, 200);
+        fake.push(Event::MapRequest(a));
+        fake.push(Event::MapRequest(b));
+        key(&fake, |a| matches!(a, Action::MoveWindowToNextMonitor));
+        fake.push(Event::UnmapNotify(a));
+        wm.run().unwrap();
+
+        assert_eq!(wm.clients.len(), 1);
+        assert_eq!(wm.window_monitors[&b], 1);
+        assert_eq!(wm.focused, Some(b));
+        assert!(fake.geometry(b).x >= 1920);
+    }
+
+    #[test]
+    fn quitting_stops_the_event_loop() {
+        let fake = Fake::new(&SCREENS);
+        let mut wm = fake.koan();
+        wm.key_bindings = BINDINGS;
+
+        let win = fake.create_window(300, 200);
+        key(&fake, |a| matches!(a, Action::QuitWM));
+        fake.push(Event::MapRequest(win));
+        wm.run().unwrap();
+
+        assert!(wm.clients.is_empty());
+        assert_eq!(fake.state.borrow().events.len(), 1);
+    }
+
+    #[test]
+    fn screen_changes_recreate_the_modelines() {
+        let fake = Fake::new(&SCREENS[..1]);
+        let mut wm = fake.koan();
+        wm.create_modelines();
+        assert_eq!(wm.modelines.len(), 1);
+
+        fake.set_outputs(&[("OUT-0", SCREENS[0]), ("OUT-1", SCREENS[1])]);
+        fake.push(Event::ScreenChange);
+        wm.run().unwrap();
+
+        assert_eq!(wm.monitors.len(), 2);
+        assert_eq!(wm.modelines.len(), 2);
+
+        // the old modeline is gone, not left behind on the screen
+        let bars = fake
+            .state
+            .borrow()
+            .windows
+            .values()
+            .filter(|w| w.attrs.override_redirect)
+            .count();
+        assert_eq!(bars, 2);
     }
 }
@@ -0,0 +1,353 @@
+use super::{
+    config::{Color, DESKTOPS},
+    modeline::{Click, Segment},
+    KoanWM, Window,
+};
+
+impl KoanWM {
+    // announces the desktops to pagers, names set by another tool are kept
+    pub fn init_desktops(&mut self) {
+        let root = self.backend.root();
+        self.backend
+            .set_cardinals(root, self.net_number_of_desktops, &[DESKTOPS.len() as u32]);
+
+        if self
+            .backend
+            .get_strings(root, self.net_desktop_names)
+            .is_empty()
+        {
+            self.backend
+                .set_strings(root, self.net_desktop_names, &self.desktop_names);
+        } else {
+            self.load_desktop_names();
+        }
+        self.update_current_desktop();
+    }
+
+    pub fn load_desktop_names(&mut self) {
+        let names = self
+            .backend
+            .get_strings(self.backend.root(), self.net_desktop_names);
+
+        self.desktop_names = DESKTOPS
+            .iter()
+            .enumerate()
+            .map(|(i, default)| match names.get(i) {
+                Some(name) if !name.is_empty() => name.clone(),
+                _ => default.to_string(),
+            })
+            .collect();
+    }
+
+    // desktop shown on the focused monitor
+    pub fn update_current_desktop(&self) {
+        if let Some(mon) = self.monitors.get(self.current_monitor) {
+            self.backend.set_cardinals(
+                self.backend.root(),
+                self.net_current_desktop,
+                &[mon.desktop as u32],
+            );
+        }
+    }
+
+    // on monitor `mon_idx` and on the desktop it shows
+    pub fn is_shown(&self, window: Window, mon_idx: usize) -> bool {
+        self.window_monitors.get(&window) == Some(&mon_idx)
+            && self
+                .monitors
+                .get(mon_idx)
+                .is_some_and(|m| self.window_desktops.get(&window) == Some(&m.desktop))
+    }
+
+    pub fn is_visible(&self, window: Window) -> bool {
+        self.window_monitors
+            .get(&window)
+            .is_some_and(|&mon_idx| self.is_shown(window, mon_idx))
+    }
+
+    // maps the clients of the shown desktops and unmaps the others
+    pub fn show_desktops(&mut self) {
+        let windows: Vec<Window> = self.clients.iter().map(|c| c.window).collect();
+
+        for window in windows {
+            let visible = self.is_visible(window);
+            if visible && self.hidden.remove(&window) {
+                self.backend.map(window);
+            } else if !visible && self.hidden.insert(window) {
+                // the UnmapNotify this causes is not the client withdrawing
+                *self.ignored_unmaps.entry(window).or_default() += 1;
+                self.backend.unmap(window);
+            }
+        }
+    }
+
+    pub fn view_desktop(&mut self, desktop: usize) {
+        let mon_idx = self.current_monitor;
+        if desktop >= DESKTOPS.len()
+            || self
+                .monitors
+                .get(mon_idx)
+                .is_none_or(|m| m.desktop == desktop)
+        {
+            return;
+        }
+
+        self.monitors[mon_idx].desktop = desktop;
+        let _ = self.layout();
+        self.focus_last_shown(mon_idx);
+        self.update_current_desktop();
+    }
+
+    pub fn move_to_desktop(&mut self, desktop: usize) {
+        let Some(window) = self.focused else { return };
+        if desktop >= DESKTOPS.len() {
+            return;
+        }
+
+        self.set_desktop(window, desktop);
+        let _ = self.layout();
+        if !self.is_visible(window) {
+            self.focus_last_shown(self.current_monitor);
+        }
+    }
+
+    pub fn set_desktop(&mut self, window: Window, desktop: usize) {
+        self.window_desktops.insert(window, desktop);
+        self.backend
+            .set_cardinals(window, self.net_wm_desktop, &[desktop as u32]);
+    }
+
+    pub fn focus_last_shown(&mut self, mon_idx: usize) {
+        let last_client = self.monitor_focus(mon_idx).or_else(|| {
+            self.clients
+                .iter()
+                .rev()
+                .find(|c| self.is_shown(c.window, mon_idx))
+                .map(|c| c.window)
+        });
+
+        match last_client {
+            Some(window) => self.focus_window(window),
+            None => {
+                self.focused = None;
+                self.update_modelines();
+            }
+        }
+    }
+
+    pub fn update_urgency(&mut self, window: Window) {
+        let urgent = self.focused != Some(window) && self.backend.is_urgent(window);
+        if let Some(client) = self.clients.iter_mut().find(|c| c.window == window) {
+            client.is_urgent = urgent;
+        }
+        self.update_modelines();
+    }
+
+    // one segment per desktop of the monitor: urgent ones in red, the shown
+    // one inverted, occupied ones bright and empty ones dimmed
+    pub fn desktop_segments(&self, mon_idx: usize) -> Vec<Segment> {
+        let shown = self.monitors.get(mon_idx).map_or(0, |m| m.desktop);
+
+        self.desktop_names
+            .iter()
+            .enumerate()
+            .map(|(desktop, name)| {
+                let mut clients = self.clients.iter().filter(|c| {
+                    self.window_monitors.get(&c.window) == Some(&mon_idx)
+                        && self.window_desktops.get(&c.window) == Some(&desktop)
+                });
+                let occupied = clients.clone().next().is_some();
+
+                let (fg, bg) = if clients.any(|c| c.is_urgent) {
+                    (Color::Secondary, Some(Color::Error))
+                } else if desktop == shown {
+                    (Color::Secondary, Some(self.modeline_color(mon_idx)))
+                } else if occupied {
+                    (self.modeline_color(mon_idx), None)
+                } else {
+                    (Color::Inactive, None)
+                };
+
+                Segment {
+                    text: format!(" {} ", name),
+                    fg: fg.hex().to_string(),
+                    bg: bg.map(|c| c.hex().to_string()),
+                    click: Some(Click::Desktop(desktop)),
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::koan::{
+        backend::{
+            fake::{Fake, SCREEN},
+            Event,
+        },
+        modeline::{Alignment, Module, ModuleType},
+    };
+    use x11rb::protocol::xproto::ButtonIndex;
+
+    #[test]
+    fn switching_desktops_hides_and_shows_windows() {
+        let fake = Fake::new(&[SCREEN]);
+        let mut wm = fake.koan();
+        let a = fake.create_window(300, 200);
+        wm.manage_window(a).unwrap();
+
+        wm.view_desktop(1);
+        assert!(!fake.with_window(a, |w| w.mapped));
+        assert_eq!(wm.focused, None);
+
+        let b = fake.create_window(300, 200);
+        wm.manage_window(b).unwrap();
+        assert_eq!(wm.window_desktops[&b], 1);
+
+        wm.view_desktop(0);
+        assert!(fake.with_window(a, |w| w.mapped));
+        assert!(!fake.with_window(b, |w| w.mapped));
+        assert_eq!(wm.focused, Some(a));
+        assert_eq!(
+            fake.with_window(1, |w| w.properties[&wm.net_current_desktop].clone()),
+            vec![0]
+        );
+    }
+
+    #[test]
+    fn split_trees_survive_a_desktop_switch() {
+        let fake = Fake::new(&[SCREEN]);
+        let mut wm = fake.koan();
+        wm.monitors[0].layout = "bsp";
+        let a = fake.create_window(300, 200);
+        let b = fake.create_window(300, 200);
+        wm.manage_window(a).unwrap();
+        wm.manage_window(b).unwrap();
+        wm.change_split_ratio(true);
+        wm.change_split_ratio(true);
+        let before = (fake.geometry(a), fake.geometry(b));
+
+        wm.view_desktop(1);
+        let c = fake.create_window(300, 200);
+        wm.manage_window(c).unwrap();
+        wm.view_desktop(0);
+
+        assert_eq!((fake.geometry(a), fake.geometry(b)), before);
+    }
+
+    #[test]
+    fn hiding_a_window_does_not_unmanage_it() {
+        let fake = Fake::new(&[SCREEN]);
+        let mut wm = fake.koan();
+        let a = fake.create_window(300, 200);
+        wm.manage_window(a).unwrap();
+        wm.view_desktop(1);
+
+        fake.push(Event::UnmapNotify(a));
+        wm.run().unwrap();
+        assert_eq!(wm.clients.len(), 1);
+
+        // the client withdrawing it later still counts
+        fake.push(Event::UnmapNotify(a));
+        wm.run().unwrap();
+        assert!(wm.clients.is_empty());
+        assert!(!wm.window_desktops.contains_key(&a));
+    }
+
+    #[test]
+    fn moving_a_window_to_another_desktop() {
+        let fake = Fake::new(&[SCREEN]);
+        let mut wm = fake.koan();
+        let a = fake.create_window(300, 200);
+        let b = fake.create_window(300, 200);
+        wm.manage_window(a).unwrap();
+        wm.manage_window(b).unwrap();
+
+        wm.move_to_desktop(2);
+
+        assert_eq!(wm.window_desktops[&b], 2);
+        assert_eq!(
+            fake.with_window(b, |w| w.properties[&wm.net_wm_desktop].clone()),
+            vec![2]
+        );
+        assert!(!fake.with_window(b, |w| w.mapped));
+        assert_eq!(wm.focused, Some(a));
+        assert_eq!(wm.tiled_clients(0).len(), 1);
+    }
+
+    #[test]
+    fn desktop_names_come_from_the_root_window() {
+        let fake = Fake::new(&[SCREEN]);
+        let mut wm = fake.koan();
+        wm.init_desktops();
+        let names = fake.with_window(1, |w| w.strings[&wm.net_desktop_names].clone());
+        assert_eq!(names, DESKTOPS);
+
+        fake.with_window(1, |w| {
+            w.strings.insert(
+                wm.net_desktop_names,
+                vec!["web".into(), "".into(), "mail".into()],
+            )
+        });
+        fake.push(Event::PropertyNotify {
+            window: 1,
+            atom: wm.net_desktop_names,
+        });
+        wm.run().unwrap();
+
+        assert_eq!(wm.desktop_names[..4], ["web", "2", "mail", "4"]);
+    }
+
+    #[test]
+    fn urgent_windows_mark_their_desktop() {
+        let fake = Fake::new(&[SCREEN]);
+        let mut wm = fake.koan();
+        let a = fake.create_window(300, 200);
+        wm.manage_window(a).unwrap();
+        wm.move_to_desktop(3);
+
+        fake.with_window(a, |w| w.urgent = true);
+        fake.push(Event::PropertyNotify {
+            window: a,
+            atom: wm.wm_hints,
+        });
+        wm.run().unwrap();
+
+        let segments = wm.desktop_segments(0);
+        assert_eq!(segments[3].bg.as_deref(), Some(Color::Error.hex()));
+        assert_eq!(segments[0].bg.as_deref(), Some(Color::Primary.hex()));
+        assert_eq!(segments[1].fg, Color::Inactive.hex());
+
+        wm.view_desktop(3);
+        assert!(!wm.clients[0].is_urgent);
+    }
+
+    #[test]
+    fn clicking_the_indicator_switches_desktop() {
+        static MODULES: &[Module] = &[Module {
+            kind: ModuleType::Desktops,
+            alignment: Alignment::Left,
+            fg: None,
+            bg: None,
+            clicks: &[],
+        }];
+        let fake = Fake::new(&[SCREEN]);
+        let mut wm = fake.koan();
+        wm.modules = MODULES;
+        wm.create_modelines();
+        wm.update_modelines();
+
+        let modeline = wm.modelines[&0];
+        let (x, _) = fake.with_window(modeline, |w| w.texts[4].clone());
+        fake.push(Event::ButtonPress {
+            window: modeline,
+            x: x + 1,
+            button: ButtonIndex::M1,
+        });
+        wm.run().unwrap();
+
+        assert_eq!(wm.monitors[0].desktop, 4);
+    }
+}
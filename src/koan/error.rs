@@ -1,4 +1,5 @@
 use thiserror::Error;
+use x11rb::errors::{ConnectError, ConnectionError, ReplyError, ReplyOrIdError};
 
 #[allow(dead_code)]
 #[derive(Error, Debug)]
@@ -9,4 +10,12 @@ pub enum KoanWMError {
     ScreenNotFound,
     #[error("generic error")]
     GenericError(String),
+    #[error("could not connect to the display: {0}")]
+    Connect(#[from] ConnectError),
+    #[error("display connection failed: {0}")]
+    Connection(#[from] ConnectionError),
+    #[error("request failed: {0}")]
+    Reply(#[from] ReplyError),
+    #[error("request failed: {0}")]
+    ReplyOrId(#[from] ReplyOrIdError),
 }
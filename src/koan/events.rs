@@ -1,25 +1,25 @@
-use x11::xlib;
-
 use super::{
+    backend::Atom,
+    bsp::Split,
     config::{CURSOR_ENTER_FOCUS, LAUNCHER, TERMINAL},
+    modeline::{Click, ClickAction},
     utils::spawn,
     window::Direction,
-    Action, KoanWM, KoanWMError, Window, KEY_BINDINGS,
+    Action, KoanWM, KoanWMError, Window,
 };
+use x11rb::protocol::xproto::{ButtonIndex, ModMask};
 
-// ke.state filters
-const RELEVANT_MODIFIERS: u32 =
-    xlib::ShiftMask | xlib::ControlMask | xlib::Mod1Mask | xlib::Mod4Mask;
+// ke.state filters, lock modifiers like caps and num lock are left out
+const RELEVANT_MODIFIERS: [ModMask; 4] =
+    [ModMask::SHIFT, ModMask::CONTROL, ModMask::M1, ModMask::M4];
 
 impl KoanWM {
-    pub fn handle_enter_notify(&mut self, ev: xlib::XEnterWindowEvent) {
+    pub fn handle_enter_notify(&mut self, window: Window, mouse_x: i32, mouse_y: i32) {
         if !CURSOR_ENTER_FOCUS {
             return;
         }
 
-        let mouse_x = ev.x_root;
-        let mouse_y = ev.y_root;
-
+        let previous = self.current_monitor;
         for (i, m) in self.monitors.iter().enumerate() {
             if mouse_x >= m.x
                 && mouse_x < m.x + m.width
@@ -31,12 +31,104 @@ impl KoanWM {
             }
         }
 
-        if self.clients.iter().any(|c| c.window == ev.window) {
-            self.focus_window(ev.window);
+        if self.clients.iter().any(|c| c.window == window) {
+            self.focus_window(window);
+        } else if self.current_monitor != previous {
+            self.update_modelines();
+        }
+    }
+
+    pub fn handle_button_press(
+        &mut self,
+        window: Window,
+        x: i32,
+        button: ButtonIndex,
+    ) -> Result<(), KoanWMError> {
+        if let Some(window) = self.tab_at(window, x) {
+            self.focus_window(window);
+            return Ok(());
+        }
+
+        let Some((mon_idx, click)) = self.modeline_click_at(window, x) else {
+            return Ok(());
+        };
+        // actions apply to the monitor of the modeline, not the focused one
+        match click {
+            Click::Desktop(desktop) if button == ButtonIndex::M1 => {
+                self.current_monitor = mon_idx;
+                self.view_desktop(desktop);
+            }
+            Click::Desktop(_) => {}
+            Click::Module(idx) => {
+                let clicks = self.modules[idx]
+                    .clicks
+                    .iter()
+                    .filter(|c| c.button == button);
+                let has_action = clicks
+                    .clone()
+                    .any(|c| matches!(c.action, ClickAction::Action(_)));
+
+                // focus follows so the action does not hit the other monitor's window
+                if has_action && mon_idx != self.current_monitor {
+                    self.current_monitor = mon_idx;
+                    self.focus_last_shown(mon_idx);
+                }
+                for click in clicks {
+                    match &click.action {
+                        ClickAction::Action(action) => self.run_action(*action)?,
+                        ClickAction::Spawn(command) => spawn(command),
+                    }
+                }
+                self.update_modelines();
+            }
+        }
+        Ok(())
+    }
+
+    pub fn handle_property_notify(&mut self, window: Window, atom: Atom) {
+        if window == self.backend.root() {
+            if atom == self.net_desktop_names {
+                self.load_desktop_names();
+                self.update_modelines();
+            } else if atom == self.wm_name || atom == self.net_wm_name {
+                self.update_modelines();
+            }
+            return;
+        }
+        if atom == self.xembed_info && self.tray_icons.contains(&window) {
+            self.place_tray();
+            return;
+        }
+        if !self.clients.iter().any(|c| c.window == window) {
+            return;
+        }
+        if atom == self.wm_hints {
+            self.update_urgency(window);
+            return;
+        }
+        if atom != self.wm_name && atom != self.net_wm_name {
+            return;
+        }
+
+        if (0..self.monitors.len()).any(|i| self.monitor_focus(i) == Some(window)) {
+            self.update_modelines();
         }
+        self.draw_tab_bars();
     }
 
     pub fn handle_unmap(&mut self, window: Window) -> Result<(), KoanWMError> {
+        // koan hid it on another desktop, the client is still there
+        if let Some(count) = self.ignored_unmaps.get_mut(&window) {
+            *count -= 1;
+            if *count == 0 {
+                self.ignored_unmaps.remove(&window);
+            }
+            return Ok(());
+        }
+        if self.remove_tray_icon(window, true) {
+            return Ok(());
+        }
+
         let client = match self.clients.iter().find(|c| c.window == window) {
             Some(c) => c,
             None => return Ok(()),
@@ -45,6 +137,9 @@ impl KoanWM {
         let was_focused = self.focused == Some(client.window);
 
         let monitor_idx = self.window_monitors.remove(&client.window).unwrap_or(0);
+        self.window_homes.remove(&client.window);
+        self.window_desktops.remove(&client.window);
+        self.hidden.remove(&client.window);
         self.clients.retain(|c| c.window != window);
 
         if was_focused {
@@ -52,8 +147,7 @@ impl KoanWM {
             let next_focus = self
                 .clients
                 .iter()
-                .filter(|&w| self.window_monitors.get(&w.window) == Some(&monitor_idx))
-                .last();
+                .rfind(|&w| self.is_shown(w.window, monitor_idx));
 
             if let Some(client) = next_focus {
                 self.focus_window(client.window);
@@ -63,141 +157,371 @@ impl KoanWM {
         Ok(())
     }
 
-    pub fn handle_keypress(&mut self, ke: xlib::XKeyEvent) -> Result<(), KoanWMError> {
-        unsafe {
-            let keysym = xlib::XKeycodeToKeysym(self.display, ke.keycode as u8, 0);
-            let clean_state = ke.state & RELEVANT_MODIFIERS;
-            for binding in KEY_BINDINGS {
-                if keysym == binding.keysym as u64 && clean_state == binding.modifiers {
-                    match binding.action {
-                        Action::SwitchLayout => {
-                            self.switch_layout();
-                        }
-                        Action::OpenTerminal => {
-                            spawn(&TERMINAL);
-                        }
-                        Action::OpenLauncher => {
-                            spawn(&LAUNCHER);
-                        }
-                        Action::QuitWM => return Err(KoanWMError::GenericError("Quit".into())),
-                        Action::CloseWindow => {
-                            if let Some(win) = self.focused {
-                                self.send_delete(win)?;
-                            }
-                        }
-                        Action::FocusUp => {
-                            println!("up")
-                        }
-                        Action::FocusDown => {
-                            println!("down")
-                        }
-                        Action::FocusRight => self.cycle_focus(true),
-                        Action::FocusLeft => self.cycle_focus(false),
-                        Action::MonitorPrev => {
-                            let _ = self.monitor_prev();
-                        }
-                        Action::MonitorNext => {
-                            let _ = self.monitor_next();
-                        }
-                        Action::MoveWindowToNextMonitor => {
-                            let _ = self.move_window_next_monitor();
-                        }
-                        Action::MoveWindowToPrevMonitor => {
-                            let _ = self.move_window_prev_monitor();
-                        }
-                        Action::MoveUp => {
-                            let client = match self.focused_client() {
-                                Some(c) => c,
-                                None => return Ok(()),
-                            };
-
-                            if client.is_float {
-                                self.move_window(Direction::Up);
-                            } else {
-                                // self.swap_window(true);
-                            }
-                        }
-                        Action::MoveDown => {
-                            let client = match self.focused_client() {
-                                Some(c) => c,
-                                None => return Ok(()),
-                            };
-
-                            if client.is_float {
-                                self.move_window(Direction::Down);
-                            } else {
-                                // self.swap_window(true);
-                            }
-                        }
-                        Action::MoveRight => {
-                            let client = match self.focused_client() {
-                                Some(c) => c,
-                                None => return Ok(()),
-                            };
-
-                            if client.is_float {
-                                self.move_window(Direction::Right);
-                            } else {
-                                self.swap_window(true);
-                            }
-                        }
-                        Action::MoveLeft => {
-                            let client = match self.focused_client() {
-                                Some(c) => c,
-                                None => return Ok(()),
-                            };
-
-                            if client.is_float {
-                                self.move_window(Direction::Left);
-                            } else {
-                                self.swap_window(false);
-                            }
-                        }
-                        Action::IncreaseHeight => {
-                            let client = match self.focused_client() {
-                                Some(c) => c,
-                                None => return Ok(()),
-                            };
-
-                            if client.is_float {
-                                self.resize_window(Direction::Down);
-                            } else {
-                                self.change_split_ratio(true);
-                            }
-                        }
-                        Action::DecreaseHeight => {
-                            let client = match self.focused_client() {
-                                Some(c) => c,
-                                None => return Ok(()),
-                            };
-
-                            if client.is_float {
-                                self.resize_window(Direction::Up);
-                            } else {
-                                self.change_split_ratio(false);
-                            }
-                        }
-                        Action::IncreaseWidth => {
-                            if let Some(client) = self.focused_client() {
-                                if client.is_float {
-                                    self.resize_window(Direction::Right);
-                                }
-                            }
-                        }
-                        Action::DecreaseWidth => {
-                            if let Some(client) = self.focused_client() {
-                                if client.is_float {
-                                    self.resize_window(Direction::Left);
-                                }
-                            }
-                        }
-                        Action::ToggleFloat => {
-                            self.toggle_float();
-                        }
-                    }
+    // hidden windows can be destroyed without a matching unmap
+    pub fn handle_destroy(&mut self, window: Window) -> Result<(), KoanWMError> {
+        self.ignored_unmaps.remove(&window);
+        if self.remove_tray_icon(window, false) {
+            return Ok(());
+        }
+        self.handle_unmap(window)
+    }
+
+    pub fn handle_keypress(&mut self, keysym: u32, state: ModMask) -> Result<(), KoanWMError> {
+        let relevant = RELEVANT_MODIFIERS
+            .iter()
+            .fold(0, |mask, &m| mask | u16::from(m));
+        let clean_state = u16::from(state) & relevant;
+        for binding in self.key_bindings {
+            if keysym == binding.keysym && clean_state == u16::from(binding.mask()) {
+                self.run_action(binding.action)?;
+            }
+        }
+        Ok(())
+    }
+
+    // shared by the key bindings and the modeline clicks
+    pub fn run_action(&mut self, action: Action) -> Result<(), KoanWMError> {
+        match action {
+            Action::SwitchLayout => {
+                self.switch_layout();
+            }
+            Action::OpenTerminal => {
+                spawn(&TERMINAL);
+            }
+            Action::OpenLauncher => {
+                spawn(&LAUNCHER);
+            }
+            Action::QuitWM => return Err(KoanWMError::GenericError("Quit".into())),
+            Action::CloseWindow => {
+                if let Some(win) = self.focused {
+                    self.send_delete(win)?;
+                }
+            }
+            // up and down walk the stack like left and right
+            Action::FocusUp => self.cycle_focus(false),
+            Action::FocusDown => self.cycle_focus(true),
+            Action::FocusRight => self.cycle_focus(true),
+            Action::FocusLeft => self.cycle_focus(false),
+            Action::MonitorPrev => self.monitor_prev(),
+            Action::MonitorNext => self.monitor_next(),
+            Action::MoveWindowToNextMonitor => self.move_window_next_monitor(),
+            Action::MoveWindowToPrevMonitor => self.move_window_prev_monitor(),
+            Action::MonitorLeft => self.monitor_towards(Direction::Left),
+            Action::MonitorRight => self.monitor_towards(Direction::Right),
+            Action::MonitorUp => self.monitor_towards(Direction::Up),
+            Action::MonitorDown => self.monitor_towards(Direction::Down),
+            Action::MoveWindowToMonitorLeft => self.move_window_towards(Direction::Left),
+            Action::MoveWindowToMonitorRight => self.move_window_towards(Direction::Right),
+            Action::MoveWindowToMonitorUp => self.move_window_towards(Direction::Up),
+            Action::MoveWindowToMonitorDown => self.move_window_towards(Direction::Down),
+            Action::ViewDesktop(desktop) => self.view_desktop(desktop),
+            Action::MoveToDesktop(desktop) => self.move_to_desktop(desktop),
+            Action::ToggleModeline => self.toggle_modeline(),
+            Action::MoveUp => {
+                let client = match self.focused_client() {
+                    Some(c) => c,
+                    None => return Ok(()),
+                };
+
+                if client.is_float {
+                    self.move_window(Direction::Up);
+                } else {
+                    // self.swap_window(true);
+                }
+            }
+            Action::MoveDown => {
+                let client = match self.focused_client() {
+                    Some(c) => c,
+                    None => return Ok(()),
+                };
+
+                if client.is_float {
+                    self.move_window(Direction::Down);
+                } else {
+                    // self.swap_window(true);
                 }
             }
+            Action::MoveRight => {
+                let client = match self.focused_client() {
+                    Some(c) => c,
+                    None => return Ok(()),
+                };
+
+                if client.is_float {
+                    self.move_window(Direction::Right);
+                } else {
+                    self.swap_window(true);
+                }
+            }
+            Action::MoveLeft => {
+                let client = match self.focused_client() {
+                    Some(c) => c,
+                    None => return Ok(()),
+                };
+
+                if client.is_float {
+                    self.move_window(Direction::Left);
+                } else {
+                    self.swap_window(false);
+                }
+            }
+            Action::IncreaseHeight => {
+                let client = match self.focused_client() {
+                    Some(c) => c,
+                    None => return Ok(()),
+                };
+
+                if client.is_float {
+                    self.resize_window(Direction::Down);
+                } else {
+                    self.change_split_ratio(true);
+                }
+            }
+            Action::DecreaseHeight => {
+                let client = match self.focused_client() {
+                    Some(c) => c,
+                    None => return Ok(()),
+                };
+
+                if client.is_float {
+                    self.resize_window(Direction::Up);
+                } else {
+                    self.change_split_ratio(false);
+                }
+            }
+            Action::IncreaseWidth => {
+                if let Some(client) = self.focused_client()
+                    && client.is_float
+                {
+                    self.resize_window(Direction::Right);
+                }
+            }
+            Action::DecreaseWidth => {
+                if let Some(client) = self.focused_client()
+                    && client.is_float
+                {
+                    self.resize_window(Direction::Left);
+                }
+            }
+            Action::ToggleFloat => {
+                self.toggle_float();
+            }
+            Action::PreselUp => self.presel(Direction::Up),
+            Action::PreselDown => self.presel(Direction::Down),
+            Action::PreselLeft => self.presel(Direction::Left),
+            Action::PreselRight => self.presel(Direction::Right),
+            Action::RotateTree => self.rotate_tree(),
+            Action::FlipHorizontal => self.flip_tree(Split::Vertical),
+            Action::FlipVertical => self.flip_tree(Split::Horizontal),
+            Action::BalanceTree => self.balance_tree(),
+            Action::IncMaster => self.change_nmaster(true),
+            Action::DecMaster => self.change_nmaster(false),
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::koan::{
+        backend::{
+            fake::{Fake, SCREEN, SCREENS},
+            Event,
+        },
+        config::KeyBinding,
+        layouts::get_layout,
+        modeline::{Alignment, Module, ModuleClick, ModuleType},
+    };
+
+    // the layout symbol switches layout, scrolling on the title cycles focus
+    static MODULES: &[Module] = &[
+        Module {
+            kind: ModuleType::Layout,
+            alignment: Alignment::Left,
+            fg: None,
+            bg: None,
+            clicks: &[ModuleClick {
+                button: ButtonIndex::M3,
+                action: ClickAction::Action(Action::SwitchLayout),
+            }],
+        },
+        Module {
+            kind: ModuleType::WindowTitle,
+            alignment: Alignment::Left,
+            fg: None,
+            bg: None,
+            clicks: &[ModuleClick {
+                button: ButtonIndex::M5,
+                action: ClickAction::Action(Action::FocusRight),
+            }],
+        },
+    ];
+
+    #[test]
+    fn unmapping_the_focused_window_focuses_another_one() {
+        let fake = Fake::new(&[SCREEN]);
+        let mut wm = fake.koan();
+        let a = fake.create_window(300, 200);
+        let b = fake.create_window(300, 200);
+        wm.manage_window(a).unwrap();
+        wm.manage_window(b).unwrap();
+
+        wm.handle_unmap(b).unwrap();
+
+        assert_eq!(wm.clients.len(), 1);
+        assert!(!wm.window_monitors.contains_key(&b));
+        assert_eq!(wm.focused, Some(a));
+        assert_eq!(fake.focus(), Some(a));
+    }
+
+    #[test]
+    fn unmapping_an_unknown_window_is_ignored() {
+        let fake = Fake::new(&[SCREEN]);
+        let mut wm = fake.koan();
+        let a = fake.create_window(300, 200);
+        wm.manage_window(a).unwrap();
+
+        wm.handle_unmap(a + 100).unwrap();
+
+        assert_eq!(wm.clients.len(), 1);
+        assert_eq!(wm.focused, Some(a));
+    }
+
+    #[test]
+    fn key_bindings_ignore_lock_modifiers() {
+        let fake = Fake::new(&[SCREEN]);
+        let mut wm = fake.koan();
+        static BINDINGS: &[KeyBinding] = &[KeyBinding {
+            keysym: x11::keysym::XK_c,
+            modifiers: &[ModMask::M1, ModMask::SHIFT],
+            action: Action::CloseWindow,
+        }];
+        wm.key_bindings = BINDINGS;
+        let a = fake.create_window(300, 200);
+        wm.manage_window(a).unwrap();
+
+        let close = &BINDINGS[0];
+        let state = close.mask() | ModMask::LOCK | ModMask::M2;
+        wm.handle_keypress(close.keysym, state).unwrap();
+
+        assert_eq!(fake.state.borrow().protocols_sent, vec![(a, wm.wm_delete)]);
+    }
+
+    #[test]
+    fn title_changes_redraw_the_modeline() {
+        let fake = Fake::new(&[SCREEN]);
+        let mut wm = fake.koan();
+        wm.modules = MODULES;
+        wm.create_modelines();
+        let win = fake.create_window(300, 200);
+        wm.manage_window(win).unwrap();
+
+        fake.set_title(win, "vim — ü.rs");
+        wm.run().unwrap();
+
+        let texts = fake.with_window(wm.modelines[&0], |w| w.texts.clone());
+        assert!(texts.iter().any(|(_, t)| t == "vim — ü.rs"));
+    }
+
+    #[test]
+    fn dialog_title_changes_redraw_the_modeline() {
+        let fake = Fake::new(&[SCREEN]);
+        let mut wm = fake.koan();
+        wm.modules = MODULES;
+        wm.create_modelines();
+        let parent = fake.create_window(300, 200);
+        let dialog = fake.create_window(400, 300);
+        fake.with_window(dialog, |w| w.transient_for = Some(parent));
+        wm.manage_window(parent).unwrap();
+        wm.manage_window(dialog).unwrap();
+        wm.focus_window(dialog);
+
+        fake.set_title(dialog, "Save as");
+        wm.run().unwrap();
+
+        let texts = fake.with_window(wm.modelines[&0], |w| w.texts.clone());
+        assert!(texts.iter().any(|(_, t)| t == "Save as"));
+    }
+
+    #[test]
+    fn modeline_clicks_run_the_module_actions() {
+        let fake = Fake::new(&[SCREEN]);
+        let mut wm = fake.koan();
+        wm.modules = MODULES;
+        wm.create_modelines();
+        let a = fake.create_window(300, 200);
+        let b = fake.create_window(300, 200);
+        wm.manage_window(a).unwrap();
+        wm.manage_window(b).unwrap();
+
+        let modeline = wm.modelines[&0];
+        let x_of = |text: &str| {
+            fake.with_window(modeline, |w| {
+                w.texts.iter().find(|(_, t)| t == text).map(|&(x, _)| x + 1)
+            })
+        };
+
+        let layout = wm.current_layout().symbol();
+        fake.push(Event::ButtonPress {
+            window: modeline,
+            x: x_of(layout).unwrap(),
+            button: ButtonIndex::M3,
+        });
+        wm.run().unwrap();
+        assert_ne!(wm.current_layout().symbol(), layout);
+
+        // buttons without an action do nothing
+        let title = wm.get_window_title(Some(b));
+        fake.push(Event::ButtonPress {
+            window: modeline,
+            x: x_of(&title).unwrap(),
+            button: ButtonIndex::M2,
+        });
+        wm.run().unwrap();
+        assert_eq!(wm.focused, Some(b));
+
+        fake.push(Event::ButtonPress {
+            window: modeline,
+            x: x_of(&title).unwrap(),
+            button: ButtonIndex::M5,
+        });
+        wm.run().unwrap();
+        assert_eq!(wm.focused, Some(a));
+    }
+
+    #[test]
+    fn modeline_clicks_focus_the_monitor_they_act_on() {
+        let fake = Fake::new(&SCREENS);
+        let mut wm = fake.koan();
+        wm.modules = MODULES;
+        wm.create_modelines();
+        let a = fake.create_window(300, 200);
+        let b = fake.create_window(300, 200);
+        wm.manage_window(a).unwrap();
+        wm.current_monitor = 1;
+        wm.manage_window(b).unwrap();
+        wm.focus_window(a);
+
+        let modeline = wm.modelines[&1];
+        let layout = wm.monitors[1].layout;
+        let symbol = get_layout(layout).symbol();
+        let x = fake.with_window(modeline, |w| {
+            w.texts
+                .iter()
+                .find(|(_, t)| t == symbol)
+                .map(|&(x, _)| x + 1)
+        });
+        fake.push(Event::ButtonPress {
+            window: modeline,
+            x: x.unwrap(),
+            button: ButtonIndex::M3,
+        });
+        wm.run().unwrap();
+
+        assert_eq!(wm.current_monitor, 1);
+        assert_eq!(wm.focused, Some(b));
+        assert_ne!(wm.monitors[1].layout, layout);
+        assert_eq!(wm.monitors[0].layout, layout);
+    }
+}
@@ -1,25 +1,546 @@
-use x11::xlib;
-
 use crate::koan::{KoanWM, KoanWMError, Window, BORDER_WIDTH};
 
 use super::{
-    config::{GAPS, MODELINE_HEIGHT},
-    window::Client,
+    bsp::{Bsp, BspTree},
+    config::{ModelinePosition, LAYOUTS, MASTER_ORIENTATION, MODELINE_HEIGHT, TAB_BAR_HEIGHT},
+    monitors::Monitor,
+    window::{Client, Direction},
 };
 
-pub enum LAYOUTS {
-    MASTER,
-    MONOCLE,
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub w: u32,
+    pub h: u32,
+}
+
+pub struct LayoutParams<'a> {
+    pub mfact: f32,
+    pub nmaster: usize,
+    pub gaps: u32,
+    pub focused: Option<Window>,
+    pub tree: &'a mut BspTree,
+}
+
+pub trait Layout: Sync {
+    fn name(&self) -> &'static str;
+    fn symbol(&self) -> &'static str;
+    // `windows` are the tiled clients of one monitor, in stacking order
+    fn arrange(
+        &self,
+        area: Rect,
+        windows: &[Window],
+        params: &mut LayoutParams,
+    ) -> Vec<(Window, Rect)>;
+
+    // new clients go to the front of the list instead of the back
+    fn insert_first(&self) -> bool {
+        false
+    }
+
+    // reserve a tab strip at the top of the area
+    fn tab_bar(&self) -> bool {
+        false
+    }
+}
+
+pub static REGISTRY: &[&dyn Layout] = &[
+    &Monocle,
+    &Tile,
+    &Spiral { dwindle: true },
+    &Spiral { dwindle: false },
+    &Bsp,
+    &CenteredMaster,
+    &ThreeColumn,
+    &Tabbed,
+    &Deck,
+];
+
+pub fn get_layout(name: &str) -> &'static dyn Layout {
+    REGISTRY
+        .iter()
+        .find(|l| l.name() == name)
+        .copied()
+        .unwrap_or(REGISTRY[0])
+}
+
+pub struct Monocle;
+
+impl Layout for Monocle {
+    fn name(&self) -> &'static str {
+        "monocle"
+    }
+
+    fn symbol(&self) -> &'static str {
+        "[M]"
+    }
+
+    fn arrange(&self, area: Rect, windows: &[Window], _: &mut LayoutParams) -> Vec<(Window, Rect)> {
+        windows.iter().map(|&w| (w, area)).collect()
+    }
+
+    fn insert_first(&self) -> bool {
+        true
+    }
+}
+
+// master area on the side given by MASTER_ORIENTATION, stack on the other one
+pub struct Tile;
+
+impl Layout for Tile {
+    fn name(&self) -> &'static str {
+        "tile"
+    }
+
+    fn symbol(&self) -> &'static str {
+        "[]="
+    }
+
+    fn arrange(
+        &self,
+        area: Rect,
+        windows: &[Window],
+        params: &mut LayoutParams,
+    ) -> Vec<(Window, Rect)> {
+        let gaps = params.gaps;
+        let (masters, stack) = windows.split_at(params.nmaster.min(windows.len()));
+        let horizontal = matches!(MASTER_ORIENTATION, Direction::Up | Direction::Down);
+
+        if masters.is_empty() || stack.is_empty() {
+            return if horizontal {
+                tile_row(windows, area, gaps)
+            } else {
+                tile_column(windows, area, gaps)
+            };
+        }
+
+        let mut out = Vec::new();
+        match MASTER_ORIENTATION {
+            Direction::Left => {
+                let (master, rest) = split_width(area, params.mfact, gaps);
+                out.extend(tile_column(masters, master, gaps));
+                out.extend(tile_column(stack, rest, gaps));
+            }
+            Direction::Right => {
+                let (rest, master) = split_width(area, 1.0 - params.mfact, gaps);
+                out.extend(tile_column(masters, master, gaps));
+                out.extend(tile_column(stack, rest, gaps));
+            }
+            Direction::Up => {
+                let (master, rest) = split_height(area, params.mfact, gaps);
+                out.extend(tile_row(masters, master, gaps));
+                out.extend(tile_row(stack, rest, gaps));
+            }
+            Direction::Down => {
+                let (rest, master) = split_height(area, 1.0 - params.mfact, gaps);
+                out.extend(tile_row(masters, master, gaps));
+                out.extend(tile_row(stack, rest, gaps));
+            }
+        }
+        out
+    }
+}
+
+// fibonacci: each client splits the remaining area, alternating width and height.
+// dwindle always leaves the rest at the right/bottom, spiral turns inwards.
+pub struct Spiral {
+    pub dwindle: bool,
+}
+
+impl Layout for Spiral {
+    fn name(&self) -> &'static str {
+        if self.dwindle {
+            "dwindle"
+        } else {
+            "spiral"
+        }
+    }
+
+    fn symbol(&self) -> &'static str {
+        if self.dwindle {
+            "[\\]"
+        } else {
+            "[@]"
+        }
+    }
+
+    fn arrange(
+        &self,
+        area: Rect,
+        windows: &[Window],
+        params: &mut LayoutParams,
+    ) -> Vec<(Window, Rect)> {
+        let n = windows.len();
+        let mut rest = area;
+        let mut out = Vec::new();
+
+        for (i, &window) in windows.iter().enumerate() {
+            if i == n - 1 {
+                out.push((window, rest));
+                break;
+            }
+
+            let ratio = if i == 0 { params.mfact } else { 0.5 };
+            let reverse = !self.dwindle && i % 4 >= 2;
+
+            let (client, remaining) = match (i % 2 == 0, reverse) {
+                (true, false) => split_width(rest, ratio, params.gaps),
+                (false, false) => split_height(rest, ratio, params.gaps),
+                (true, true) => {
+                    let (remaining, client) = split_width(rest, 1.0 - ratio, params.gaps);
+                    (client, remaining)
+                }
+                (false, true) => {
+                    let (remaining, client) = split_height(rest, 1.0 - ratio, params.gaps);
+                    (client, remaining)
+                }
+            };
+
+            out.push((window, client));
+            rest = remaining;
+        }
+        out
+    }
+}
+
+// master column in the middle, stack alternating between right and left columns
+pub struct CenteredMaster;
+
+impl Layout for CenteredMaster {
+    fn name(&self) -> &'static str {
+        "centeredmaster"
+    }
+
+    fn symbol(&self) -> &'static str {
+        "|M|"
+    }
+
+    fn arrange(
+        &self,
+        area: Rect,
+        windows: &[Window],
+        params: &mut LayoutParams,
+    ) -> Vec<(Window, Rect)> {
+        let gaps = params.gaps;
+        let (masters, stack) = windows.split_at(params.nmaster.min(windows.len()));
+
+        if masters.is_empty() || stack.is_empty() {
+            return tile_column(windows, area, gaps);
+        }
+
+        let mut out = Vec::new();
+
+        if stack.len() == 1 {
+            let (master, rest) = split_width(area, params.mfact, gaps);
+            out.extend(tile_column(masters, master, gaps));
+            out.extend(tile_column(stack, rest, gaps));
+            return out;
+        }
+
+        let master_w = (area.w.saturating_sub(gaps * 2) as f32 * params.mfact) as u32;
+        let left_w = area.w.saturating_sub(master_w + gaps * 2) / 2;
+
+        let (left_area, rest) = split_at_width(area, left_w, gaps);
+        let (master_area, right_area) = split_at_width(rest, master_w, gaps);
+
+        let right: Vec<Window> = stack.iter().step_by(2).copied().collect();
+        let left: Vec<Window> = stack.iter().skip(1).step_by(2).copied().collect();
+
+        out.extend(tile_column(masters, master_area, gaps));
+        out.extend(tile_column(&left, left_area, gaps));
+        out.extend(tile_column(&right, right_area, gaps));
+        out
+    }
+}
+
+// master column on the left, stack split between a middle and a right column
+pub struct ThreeColumn;
+
+impl Layout for ThreeColumn {
+    fn name(&self) -> &'static str {
+        "threecol"
+    }
+
+    fn symbol(&self) -> &'static str {
+        "|||"
+    }
+
+    fn arrange(
+        &self,
+        area: Rect,
+        windows: &[Window],
+        params: &mut LayoutParams,
+    ) -> Vec<(Window, Rect)> {
+        let gaps = params.gaps;
+        let (masters, stack) = windows.split_at(params.nmaster.min(windows.len()));
+
+        if stack.is_empty() {
+            return tile_column(masters, area, gaps);
+        }
+
+        let mut out = Vec::new();
+        let mut rest = area;
+
+        if !masters.is_empty() {
+            let (master, remaining) = if stack.len() > 1 {
+                // leave room for the gap between the two stack columns
+                let master_w = (area.w.saturating_sub(gaps * 2) as f32 * params.mfact) as u32;
+                split_at_width(area, master_w, gaps)
+            } else {
+                split_width(area, params.mfact, gaps)
+            };
+            out.extend(tile_column(masters, master, gaps));
+            rest = remaining;
+        }
+
+        if stack.len() == 1 {
+            out.extend(tile_column(stack, rest, gaps));
+            return out;
+        }
+
+        let (middle, right) = stack.split_at(stack.len().div_ceil(2));
+        let (middle_area, right_area) = split_width(rest, 0.5, gaps);
+
+        out.extend(tile_column(middle, middle_area, gaps));
+        out.extend(tile_column(right, right_area, gaps));
+        out
+    }
+}
+
+// monocle with a tab strip on top, one tab per client
+pub struct Tabbed;
+
+impl Layout for Tabbed {
+    fn name(&self) -> &'static str {
+        "tabbed"
+    }
+
+    fn symbol(&self) -> &'static str {
+        "[T]"
+    }
+
+    fn arrange(
+        &self,
+        area: Rect,
+        windows: &[Window],
+        params: &mut LayoutParams,
+    ) -> Vec<(Window, Rect)> {
+        let offset = (TAB_BAR_HEIGHT + params.gaps).min(area.h);
+        let client = Rect {
+            y: area.y + offset as i32,
+            h: area.h - offset,
+            ..area
+        };
+        windows.iter().map(|&w| (w, client)).collect()
+    }
+
+    fn tab_bar(&self) -> bool {
+        true
+    }
+}
+
+// master column stays visible, the stack is shown one client at a time
+pub struct Deck;
+
+impl Layout for Deck {
+    fn name(&self) -> &'static str {
+        "deck"
+    }
+
+    fn symbol(&self) -> &'static str {
+        "[D]"
+    }
+
+    fn arrange(
+        &self,
+        area: Rect,
+        windows: &[Window],
+        params: &mut LayoutParams,
+    ) -> Vec<(Window, Rect)> {
+        let gaps = params.gaps;
+        let (masters, stack) = windows.split_at(params.nmaster.min(windows.len()));
+
+        if stack.is_empty() {
+            return tile_column(masters, area, gaps);
+        }
+
+        let mut out = Vec::new();
+        let mut rest = area;
+
+        if !masters.is_empty() {
+            let (master, remaining) = split_width(area, params.mfact, gaps);
+            out.extend(tile_column(masters, master, gaps));
+            rest = remaining;
+        }
+
+        out.extend(stack.iter().map(|&w| (w, rest)));
+        out
+    }
+}
+
+// what is left of a monitor once the modeline and the outer gaps are taken out
+pub fn usable_area(mon: &Monitor) -> Rect {
+    let (top_bar, bottom_bar) = match mon.modeline_position() {
+        Some(ModelinePosition::Top) => (MODELINE_HEIGHT, 0),
+        Some(ModelinePosition::Bottom) => (0, MODELINE_HEIGHT),
+        None => (0, 0),
+    };
+
+    Rect {
+        x: mon.x + mon.gaps as i32,
+        y: mon.y + (top_bar + mon.gaps) as i32,
+        w: (mon.width.max(0) as u32).saturating_sub(mon.gaps * 2),
+        h: (mon.height.max(0) as u32).saturating_sub(top_bar + bottom_bar + mon.gaps * 2),
+    }
+}
+
+// X size of a client inside `rect`, borders drawn outside and never zero sized
+pub fn without_border(rect: Rect) -> Rect {
+    Rect {
+        w: rect.w.saturating_sub(BORDER_WIDTH * 2).max(1),
+        h: rect.h.saturating_sub(BORDER_WIDTH * 2).max(1),
+        ..rect
+    }
+}
+
+// final geometry of the tiled `windows` of a monitor, ready for XMoveResizeWindow
+pub fn arrange_monitor(
+    mon: &Monitor,
+    windows: &[Window],
+    focused: Option<Window>,
+    tree: &mut BspTree,
+) -> Vec<(Window, Rect)> {
+    let mut params = LayoutParams {
+        mfact: mon.mfact,
+        nmaster: mon.nmaster,
+        gaps: mon.gaps,
+        focused,
+        tree,
+    };
+
+    get_layout(mon.layout)
+        .arrange(usable_area(mon), windows, &mut params)
+        .into_iter()
+        .map(|(window, rect)| (window, without_border(rect)))
+        .collect()
+}
+
+// splits `area` side by side, the left part takes `ratio` of the width minus the gap
+pub fn split_width(area: Rect, ratio: f32, gaps: u32) -> (Rect, Rect) {
+    let left_w = (area.w.saturating_sub(gaps) as f32 * ratio) as u32;
+    split_at_width(area, left_w, gaps)
+}
+
+fn split_at_width(area: Rect, left_w: u32, gaps: u32) -> (Rect, Rect) {
+    let offset = (left_w + gaps).min(area.w);
+    let left = Rect {
+        w: left_w.min(area.w),
+        ..area
+    };
+    let right = Rect {
+        x: area.x + offset as i32,
+        w: area.w - offset,
+        ..area
+    };
+    (left, right)
+}
+
+// same as split_width but one above the other
+pub fn split_height(area: Rect, ratio: f32, gaps: u32) -> (Rect, Rect) {
+    let top_h = (area.h.saturating_sub(gaps) as f32 * ratio) as u32;
+    let offset = (top_h + gaps).min(area.h);
+    let top = Rect { h: top_h, ..area };
+    let bottom = Rect {
+        y: area.y + offset as i32,
+        h: area.h - offset,
+        ..area
+    };
+    (top, bottom)
+}
+
+// stacks windows vertically inside `area`, the last one takes the leftover pixels
+pub fn tile_column(windows: &[Window], area: Rect, gaps: u32) -> Vec<(Window, Rect)> {
+    let n = windows.len() as u32;
+
+    if n == 0 {
+        return Vec::new();
+    }
+
+    let cell_h = area.h.saturating_sub(gaps * (n - 1)) / n;
+
+    windows
+        .iter()
+        .enumerate()
+        .map(|(i, &window)| {
+            // clamped so that clients that do not fit pile up at the bottom
+            let offset = (i as u32 * (cell_h + gaps)).min(area.h);
+            let h = if i as u32 == n - 1 {
+                area.h - offset
+            } else {
+                cell_h.min(area.h - offset)
+            };
+            (
+                window,
+                Rect {
+                    y: area.y + offset as i32,
+                    h,
+                    ..area
+                },
+            )
+        })
+        .collect()
+}
+
+// same as tile_column but side by side
+pub fn tile_row(windows: &[Window], area: Rect, gaps: u32) -> Vec<(Window, Rect)> {
+    let n = windows.len() as u32;
+
+    if n == 0 {
+        return Vec::new();
+    }
+
+    let cell_w = area.w.saturating_sub(gaps * (n - 1)) / n;
+
+    windows
+        .iter()
+        .enumerate()
+        .map(|(i, &window)| {
+            let offset = (i as u32 * (cell_w + gaps)).min(area.w);
+            let w = if i as u32 == n - 1 {
+                area.w - offset
+            } else {
+                cell_w.min(area.w - offset)
+            };
+            (
+                window,
+                Rect {
+                    x: area.x + offset as i32,
+                    w,
+                    ..area
+                },
+            )
+        })
+        .collect()
 }
 
 impl KoanWM {
     pub fn change_split_ratio(&mut self, increase: bool) {
         let step = 0.05;
 
-        if increase {
-            self.mfact = (self.mfact + step).min(0.90);
+        let key = self.tree_key(self.current_monitor);
+        let mon = &mut self.monitors[self.current_monitor];
+
+        if mon.layout == Bsp.name() {
+            if let Some(focused) = self.focused
+                && let Some(tree) = self.trees.get_mut(&key)
+            {
+                tree.resize(focused, if increase { step } else { -step });
+            }
+        } else if increase {
+            mon.mfact = (mon.mfact + step).min(0.90);
         } else {
-            self.mfact = (self.mfact - step).max(0.10);
+            mon.mfact = (mon.mfact - step).max(0.10);
         }
 
         if let Err(e) = self.layout() {
@@ -27,122 +548,276 @@ impl KoanWM {
         }
     }
 
+    pub fn change_nmaster(&mut self, increase: bool) {
+        let mon = &mut self.monitors[self.current_monitor];
+
+        if increase {
+            mon.nmaster += 1;
+        } else {
+            mon.nmaster = mon.nmaster.saturating_sub(1);
+        }
+
+        let _ = self.layout();
+    }
+
+    // cycles through the layouts listed in config::LAYOUTS
     pub fn switch_layout(&mut self) {
-        self.layout = match self.layout {
-            LAYOUTS::MASTER => LAYOUTS::MONOCLE,
-            LAYOUTS::MONOCLE => LAYOUTS::MASTER,
+        let mon = &mut self.monitors[self.current_monitor];
+        let next = match LAYOUTS.iter().position(|&name| name == mon.layout) {
+            Some(pos) => LAYOUTS[(pos + 1) % LAYOUTS.len()],
+            None => LAYOUTS[0],
         };
+        mon.layout = get_layout(next).name();
+
         let _ = self.layout();
+        self.update_modelines();
     }
 
-    pub fn monocle(&mut self, top: i32, bottom: u32) {
-        for (mon_idx, mon) in self.monitors.iter().enumerate() {
-            let mon_windows: Vec<&Client> = self
-                .clients
+    pub fn current_layout(&self) -> &'static dyn Layout {
+        get_layout(self.monitors[self.current_monitor].layout)
+    }
+
+    pub fn layout(&mut self) -> Result<(), KoanWMError> {
+        self.show_desktops();
+        for mon in self.monitors.clone() {
+            let windows: Vec<Window> = self
+                .tiled_clients(mon.index)
                 .iter()
-                .filter(|c| !c.is_float)
-                .filter(|c| self.window_monitors.get(&c.window) == Some(&mon_idx))
+                .map(|c| c.window)
                 .collect();
+            let focused = self.focused.filter(|w| windows.contains(w));
+            let tree = self.trees.entry((mon.index, mon.desktop)).or_default();
 
-            if mon_windows.is_empty() {
-                continue;
+            for (window, rect) in arrange_monitor(&mon, &windows, focused, tree) {
+                self.apply_geometry(window, rect);
             }
 
-            for client in mon_windows {
-                self.apply_geometry(
-                    client.window,
-                    mon.x + GAPS as i32,
-                    mon.y + top,
-                    mon.width as u32 - (GAPS * 2),
-                    mon.height as u32 - bottom,
-                );
+            let area = usable_area(&mon);
+            if get_layout(mon.layout).tab_bar() && !windows.is_empty() {
+                self.place_tab_bar(mon.index, area.x, area.y, area.w);
+            } else {
+                self.hide_tab_bar(mon.index);
+            }
+        }
+
+        self.draw_tab_bars();
+
+        for client in self.clients.iter().filter(|c| c.is_float) {
+            if self.is_shown(client.window, self.current_monitor) {
+                self.backend.raise(client.window);
             }
         }
+
+        Ok(())
     }
 
-    pub fn master_and_stack(&mut self, top: i32, bottom: u32) {
-        for (mon_idx, mon) in self.monitors.iter().enumerate() {
-            let mon_windows: Vec<&Client> = self
-                .clients
-                .iter()
-                .filter(|c| !c.is_float)
-                .filter(|&c| self.window_monitors.get(&c.window) == Some(&mon_idx))
-                .collect();
+    pub fn tiled_clients(&self, mon_idx: usize) -> Vec<&Client> {
+        self.clients
+            .iter()
+            .filter(|c| !c.is_float)
+            .filter(|c| self.is_shown(c.window, mon_idx))
+            .collect()
+    }
 
-            let n = mon_windows.len();
+    pub fn apply_geometry(&self, win: Window, rect: Rect) {
+        self.backend.move_resize(win, rect);
+        self.backend.set_border_width(win, BORDER_WIDTH);
+    }
+}
 
-            if n == 0 {
-                continue;
-            }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-            if n == 1 {
-                self.apply_geometry(
-                    mon_windows[0].window,
-                    mon.x + GAPS as i32,
-                    mon.y + top,
-                    mon.width as u32 - (GAPS * 2),
-                    mon.height as u32 - bottom,
-                );
-            } else {
-                let master_w_raw = (mon.width as f32 * self.mfact) as u32;
-
-                self.apply_geometry(
-                    mon_windows[0].window,
-                    mon.x + GAPS as i32,
-                    mon.y + top,
-                    master_w_raw - (GAPS * 2),
-                    mon.height as u32 - bottom,
-                );
-
-                let stack_x = mon.x + master_w_raw as i32;
-                let stack_w_raw = mon.width as u32 - master_w_raw;
-                let stack_h_total = mon.height as u32 / (n as u32 - 1);
-
-                for (i, &client) in mon_windows.iter().skip(1).enumerate() {
-                    let stack_y = mon.y + (i as i32 * stack_h_total as i32);
-
-                    let height_adjustment = if i == (n - 1) - 1 { bottom } else { GAPS * 2 };
-
-                    self.apply_geometry(
-                        client.window,
-                        stack_x + (GAPS / 2) as i32,
-                        stack_y + top as i32,
-                        stack_w_raw - GAPS - (GAPS / 2),
-                        (stack_h_total - height_adjustment) as u32,
-                    );
-                }
-            }
+    fn area(w: u32, h: u32) -> Rect {
+        Rect { x: 7, y: 19, w, h }
+    }
+
+    fn windows(n: usize) -> Vec<Window> {
+        (1..=n as Window).collect()
+    }
+
+    fn arrange(name: &str, area: Rect, windows: &[Window], nmaster: usize) -> Vec<(Window, Rect)> {
+        let mut tree = BspTree::default();
+        let mut params = LayoutParams {
+            mfact: 0.55,
+            nmaster,
+            gaps: 4,
+            focused: None,
+            tree: &mut tree,
+        };
+        get_layout(name).arrange(area, windows, &mut params)
+    }
+
+    fn inside(r: Rect, area: Rect) -> bool {
+        r.x >= area.x
+            && r.y >= area.y
+            && r.x + r.w as i32 <= area.x + area.w as i32
+            && r.y + r.h as i32 <= area.y + area.h as i32
+    }
+
+    fn overlap(a: Rect, b: Rect) -> bool {
+        a.x < b.x + b.w as i32
+            && b.x < a.x + a.w as i32
+            && a.y < b.y + b.h as i32
+            && b.y < a.y + a.h as i32
+    }
+
+    fn bottom(r: Rect) -> i32 {
+        r.y + r.h as i32
+    }
+
+    #[test]
+    fn registry_names_match_config() {
+        for name in LAYOUTS {
+            assert_eq!(get_layout(name).name(), *name);
         }
+        assert_eq!(get_layout("nope").name(), REGISTRY[0].name());
     }
 
-    pub fn layout(&mut self) -> Result<(), KoanWMError> {
-        let top = (GAPS + MODELINE_HEIGHT) as i32;
-        let bottom = (MODELINE_HEIGHT + GAPS * 2) as u32;
+    #[test]
+    fn column_uses_every_pixel_with_odd_sizes() {
+        for h in [1001, 1079, 767, 13] {
+            let out = tile_column(&windows(3), area(640, h), 4);
+            let used: u32 = out.iter().map(|(_, r)| r.h).sum::<u32>() + 4 * 2;
+            assert_eq!(used, h);
+            assert_eq!(bottom(out[2].1), bottom(area(640, h)));
+        }
+    }
 
-        match self.layout {
-            LAYOUTS::MASTER => self.master_and_stack(top, bottom),
-            LAYOUTS::MONOCLE => self.monocle(top, bottom),
+    #[test]
+    fn row_uses_every_pixel_with_odd_sizes() {
+        for w in [1921, 2559, 801] {
+            let out = tile_row(&windows(4), area(w, 500), 4);
+            let used: u32 = out.iter().map(|(_, r)| r.w).sum::<u32>() + 4 * 3;
+            assert_eq!(used, w);
         }
+    }
 
-        for client in self.clients.iter().filter(|c| c.is_float) {
-            if self.window_monitors.get(&client.window) == Some(&self.current_monitor) {
-                unsafe {
-                    self.center_window(client.window);
-                    xlib::XRaiseWindow(self.display, client.window);
+    #[test]
+    fn tile_stack_reaches_the_bottom_edge() {
+        let a = area(1913, 1047);
+        for n in 2..10 {
+            let out = arrange("tile", a, &windows(n), 1);
+            assert_eq!(bottom(out[0].1), bottom(a));
+            assert_eq!(bottom(out[n - 1].1), bottom(a));
+        }
+    }
+
+    #[test]
+    fn nmaster_shares_the_master_column() {
+        let a = area(1000, 800);
+        let out = arrange("tile", a, &windows(5), 2);
+        assert_eq!(out[0].1.x, out[1].1.x);
+        assert_eq!(out[0].1.w, out[1].1.w);
+        assert!(out[2].1.x > out[0].1.x);
+
+        // no masters at all, every client in the stack
+        let out = arrange("tile", a, &windows(3), 0);
+        assert!(out.iter().all(|(_, r)| r.w == a.w));
+    }
+
+    #[test]
+    fn every_layout_places_each_window_once_inside_the_area() {
+        for layout in REGISTRY {
+            for a in [area(1920, 1080), area(1921, 1047), area(3, 2), area(0, 0)] {
+                for n in 0..30 {
+                    let wins = windows(n);
+                    let out = arrange(layout.name(), a, &wins, 1);
+
+                    let mut placed: Vec<Window> = out.iter().map(|(w, _)| *w).collect();
+                    placed.sort();
+                    assert_eq!(placed, wins, "{} with {} clients", layout.name(), n);
+
+                    for (_, r) in out {
+                        assert!(inside(r, a), "{} {:?} outside {:?}", layout.name(), r, a);
+                    }
                 }
             }
         }
+    }
 
-        Ok(())
+    #[test]
+    fn tiling_layouts_do_not_overlap() {
+        let a = area(2561, 1439);
+        for name in [
+            "tile",
+            "dwindle",
+            "spiral",
+            "bsp",
+            "centeredmaster",
+            "threecol",
+        ] {
+            for n in 1..16 {
+                let out = arrange(name, a, &windows(n), 2);
+                for (i, (_, r1)) in out.iter().enumerate() {
+                    for (_, r2) in out.iter().skip(i + 1) {
+                        assert!(!overlap(*r1, *r2), "{} with {} clients", name, n);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn many_clients_on_a_small_monitor() {
+        let a = area(100, 60);
+        for name in ["tile", "threecol", "centeredmaster"] {
+            let out = arrange(name, a, &windows(200), 3);
+            assert_eq!(out.len(), 200);
+            assert!(out.iter().all(|(_, r)| inside(*r, a)));
+        }
+    }
+
+    #[test]
+    fn usable_area_does_not_underflow_on_tiny_monitors() {
+        let mon = Monitor::new(0, 0, 5, 5, 0);
+        let a = usable_area(&mon);
+        assert_eq!((a.w, a.h), (0, 0));
+
+        let mon = Monitor::new(0, 0, -1, -1, 0);
+        let a = usable_area(&mon);
+        assert_eq!((a.w, a.h), (0, 0));
+    }
+
+    #[test]
+    fn usable_area_leaves_room_for_the_modeline() {
+        let mon = Monitor::new(1920, 0, 1920, 1080, 1);
+        let a = usable_area(&mon);
+        assert_eq!(a.x, 1920 + mon.gaps as i32);
+        assert_eq!(a.y, (MODELINE_HEIGHT + mon.gaps) as i32);
+        assert_eq!(bottom(a), 1080 - mon.gaps as i32);
     }
 
-    fn apply_geometry(&self, win: Window, x: i32, y: i32, w: u32, h: u32) {
-        let final_w = w.saturating_sub(BORDER_WIDTH * 2);
-        let final_h = h.saturating_sub(BORDER_WIDTH * 2);
+    #[test]
+    fn usable_area_follows_the_modeline_position() {
+        let mut mon = Monitor::new(0, 0, 1920, 1080, 0);
+        mon.modeline = Some(ModelinePosition::Bottom);
+        let a = usable_area(&mon);
+        assert_eq!(a.y, mon.gaps as i32);
+        assert_eq!(bottom(a), 1080 - (MODELINE_HEIGHT + mon.gaps) as i32);
 
-        unsafe {
-            xlib::XMoveResizeWindow(self.display, win, x, y, final_w, final_h);
-            xlib::XSetWindowBorderWidth(self.display, win, BORDER_WIDTH);
+        mon.modeline = None;
+        let a = usable_area(&mon);
+        assert_eq!(a.y, mon.gaps as i32);
+        assert_eq!(bottom(a), 1080 - mon.gaps as i32);
+    }
+
+    #[test]
+    fn arranged_clients_are_never_zero_sized() {
+        let mut mon = Monitor::new(0, 0, 30, 30, 0);
+        mon.layout = "tile";
+        let mut tree = BspTree::default();
+        for (_, r) in arrange_monitor(&mon, &windows(6), None, &mut tree) {
+            assert!(r.w >= 1 && r.h >= 1);
         }
     }
+
+    #[test]
+    fn borders_are_taken_out_of_the_geometry() {
+        let r = without_border(area(100, 50));
+        assert_eq!(r.w, 100 - BORDER_WIDTH * 2);
+        assert_eq!(r.h, 50 - BORDER_WIDTH * 2);
+        assert_eq!((r.x, r.y), (7, 19));
+    }
 }
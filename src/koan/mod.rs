@@ -1,3 +1,4 @@
+pub mod bsp;
 pub mod config;
 pub mod core;
 pub mod error;
@@ -8,9 +9,16 @@ pub mod utils;
 pub mod window;
 pub mod apps;
 pub mod modeline;
+pub mod tabbar;
+pub mod backend;
+pub mod desktops;
+pub mod command;
+pub mod sysinfo;
+pub mod status;
+pub mod tray;
 
 pub use core::KoanWM;
 pub use window::Window;
 pub use error::KoanWMError;
-pub use config::{BORDER_WIDTH, KEY_BINDINGS, Action};
-pub use utils::{get_pixel_from_color, spawn};
+pub use config::{BORDER_WIDTH, Action};
+pub use utils::spawn;
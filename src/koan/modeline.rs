@@ -1,18 +1,69 @@
 use super::{
-    config::{Color, MODELINE_HEIGHT, MODELINE_UPDATE_TIME},
-    get_pixel_from_color, KoanWM, Window,
+    backend::Backend,
+    config::{
+        AppCommand, Color, ModelinePosition, MODELINE_HEIGHT, MODELINE_PADDING, MODELINE_SEPARATOR,
+        MODELINE_UPDATE_TIME,
+    },
+    layouts::{get_layout, Rect},
+    Action, KoanWM, Window,
 };
 use chrono::prelude::*;
-use std::{ffi::CString, thread, time::Duration};
-use x11::xlib;
+use std::{collections::HashMap, time::Duration};
+use x11rb::protocol::xproto::ButtonIndex;
 
+const ELLIPSIS: &str = "…";
+
+#[allow(dead_code)]
 pub enum ModuleType {
     Clock,
+    Desktops,
     WindowTitle,
     ClientCount,
+    Layout,
     Custom(fn() -> String),
+    // root window name set with `xsetroot -name`, status2d colors included
+    RootName,
+    // shell command run every `interval` seconds, or kept running and
+    // showing the last line it printed when there is no interval
+    Command {
+        command: &'static str,
+        interval: Option<u64>,
+    },
+    // first battery of /sys/class/power_supply, {capacity} and {status}.
+    // Alerts at `low` percent or less while discharging
+    Battery {
+        format: &'static str,
+        low: u32,
+        interval: u64,
+    },
+    // {usage} percent since the previous reading, alerts from `high`
+    Cpu {
+        format: &'static str,
+        high: u32,
+        interval: u64,
+    },
+    // {used}, {total} and {percent} of /proc/meminfo, alerts from `high` percent
+    Memory {
+        format: &'static str,
+        high: u32,
+        interval: u64,
+    },
+    // {down} and {up} per second of `interface`, all but lo when it is empty
+    Network {
+        interface: &'static str,
+        format: &'static str,
+        interval: u64,
+    },
+    // {volume} of an amixer control, `muted` is used instead when it is off
+    Volume {
+        control: &'static str,
+        format: &'static str,
+        muted: &'static str,
+        interval: u64,
+    },
 }
 
+#[allow(dead_code)]
 pub enum Alignment {
     Left,
     Center,
@@ -22,222 +73,500 @@ pub enum Alignment {
 pub struct Module {
     pub kind: ModuleType,
     pub alignment: Alignment,
+    pub fg: Option<&'static str>, // hex colors used instead of the modeline ones
+    pub bg: Option<&'static str>,
+    pub clicks: &'static [ModuleClick],
 }
 
-impl Module {
-    pub fn get_text(&self, focused_title: &str, client_count: usize) -> String {
-        match self.kind {
-            ModuleType::Clock => Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
-            ModuleType::WindowTitle => focused_title.to_string(),
-            ModuleType::ClientCount => format!("[{}]", client_count),
-            ModuleType::Custom(f) => f(),
+// ButtonIndex::M4 and M5 are the scroll wheel
+pub struct ModuleClick {
+    pub button: ButtonIndex,
+    pub action: ClickAction,
+}
+
+pub enum ClickAction {
+    Action(Action),
+    Spawn(AppCommand),
+}
+
+// what clicking a piece of the modeline does
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Click {
+    Desktop(usize),
+    Module(usize), // index into KoanWM::modules
+}
+
+// a piece of text drawn by a module, with its own colors
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segment {
+    pub text: String,
+    pub fg: String,
+    pub bg: Option<String>,
+    pub click: Option<Click>,
+}
+
+impl Segment {
+    pub fn plain(text: String, color: Color) -> Self {
+        Self {
+            text,
+            fg: color.hex().to_string(),
+            bg: None,
+            click: None,
         }
     }
 }
 
 impl KoanWM {
     pub fn create_modelines(&mut self) {
-        self.modelines.clear();
-        self.modules = vec![
-            Module {
-                kind: ModuleType::WindowTitle,
-                alignment: Alignment::Left,
-            },
-            Module {
-                kind: ModuleType::ClientCount,
-                alignment: Alignment::Left,
-            },
-            Module {
-                kind: ModuleType::Clock,
-                alignment: Alignment::Right,
-            },
-        ];
-
-        for mon in self.monitors.iter() {
-            unsafe {
-                let root = xlib::XDefaultRootWindow(self.display);
-                let mut attributes: xlib::XSetWindowAttributes = std::mem::zeroed();
-                attributes.event_mask = xlib::ExposureMask;
-                attributes.background_pixel =
-                    get_pixel_from_color(self.display, Color::Secondary.hex());
-                attributes.override_redirect = 1;
-                let modeline = xlib::XCreateWindow(
-                    self.display,
-                    root,
-                    mon.x,
-                    mon.y,
-                    mon.width as u32,
-                    MODELINE_HEIGHT,
-                    0,
-                    xlib::CopyFromParent,
-                    xlib::InputOutput as u32,
-                    std::ptr::null_mut(),
-                    xlib::CWBackPixel | xlib::CWEventMask | xlib::CWOverrideRedirect,
-                    &mut attributes,
-                );
-                let gc = xlib::XCreateGC(self.display, modeline, 0, std::ptr::null_mut());
-                xlib::XSetForeground(
-                    self.display,
-                    gc,
-                    get_pixel_from_color(self.display, Color::Primary.hex()),
-                );
-                xlib::XMapWindow(self.display, modeline);
-                self.modelines.push(modeline);
-                self.gc = gc;
-            }
+        self.destroy_modelines();
+
+        for (mon_idx, mon) in self.monitors.iter().enumerate() {
+            let y = match mon.modeline_position() {
+                Some(ModelinePosition::Top) => mon.y,
+                Some(ModelinePosition::Bottom) => mon.y + mon.height - MODELINE_HEIGHT as i32,
+                None => continue,
+            };
+            let rect = Rect {
+                x: mon.x,
+                y,
+                w: mon.width as u32,
+                h: MODELINE_HEIGHT,
+            };
+            let modeline = self.backend.create_bar(rect, Color::Secondary.hex(), true);
+            self.backend.map(modeline);
+            self.modelines.insert(mon_idx, modeline);
         }
     }
 
-    pub fn update_modelines(&self) {
-        if self.modelines.is_empty() {
-            return;
-        }
-        let focused_title = self.get_focus_title();
-        let client_count = self.clients.len();
-
-        let char_width: i32 = 8;
-        let inter_padding: i32 = 0;
-        let margin: i32 = 10;
-
-        let mut left_texts: Vec<String> = vec![];
-        let mut center_texts: Vec<String> = vec![];
-        let mut right_texts: Vec<String> = vec![];
-
-        for module in &self.modules {
-            let text = module.get_text(&focused_title, client_count);
-            match module.alignment {
-                Alignment::Left => left_texts.push(text),
-                Alignment::Center => center_texts.push(text),
-                Alignment::Right => right_texts.push(text),
+    pub fn module_segments(&self, idx: usize, mon_idx: usize) -> Vec<Segment> {
+        let mut segments = self.module_content(idx, mon_idx);
+        let module = &self.modules[idx];
+
+        // only text in the focused color changes, unfocused monitors stay dimmed
+        for segment in segments.iter_mut() {
+            if let Some(fg) = module.fg.filter(|_| segment.fg == Color::Primary.hex()) {
+                segment.fg = fg.to_string();
+            }
+            if segment.bg.is_none() {
+                segment.bg = module.bg.map(str::to_string);
             }
         }
 
-        let compute_width = |texts: &[String]| -> i32 {
-            if texts.is_empty() {
-                return 0;
+        if !module.clicks.is_empty() {
+            for segment in segments.iter_mut().filter(|s| s.click.is_none()) {
+                segment.click = Some(Click::Module(idx));
             }
-            let mut w = 0;
-            for (i, t) in texts.iter().enumerate() {
-                w += t.len() as i32 * char_width;
-                if i < texts.len() - 1 {
-                    w += inter_padding;
-                }
+        }
+        segments
+    }
+
+    fn module_content(&self, idx: usize, mon_idx: usize) -> Vec<Segment> {
+        let text = match self.modules[idx].kind {
+            ModuleType::Desktops => return self.desktop_segments(mon_idx),
+            ModuleType::RootName => return self.root_name_segments(mon_idx),
+            ModuleType::Clock => Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            ModuleType::WindowTitle => self.get_window_title(self.monitor_focus(mon_idx)),
+            ModuleType::ClientCount => format!(
+                "[{}]",
+                self.clients
+                    .iter()
+                    .filter(|c| self.is_shown(c.window, mon_idx))
+                    .count()
+            ),
+            ModuleType::Layout => self
+                .monitors
+                .get(mon_idx)
+                .map_or("", |m| get_layout(m.layout).symbol())
+                .to_string(),
+            ModuleType::Custom(f) => f(),
+            // updated on their own thread
+            _ => {
+                let reading = self.module_readings.get(&idx).cloned().unwrap_or_default();
+                let color = match reading.alert {
+                    true => Color::Error,
+                    false => self.modeline_color(mon_idx),
+                };
+                return vec![Segment::plain(reading.text, color)];
             }
-            w
         };
+        vec![Segment::plain(text, self.modeline_color(mon_idx))]
+    }
 
-        let left_w = compute_width(&left_texts);
-        let center_w = compute_width(&center_texts);
-        let right_w = compute_width(&right_texts);
-
-        for &modeline in self.modelines.iter() {
-            let mut root_return: xlib::Window = 0;
-            let mut x_return: i32 = 0;
-            let mut y_return: i32 = 0;
-            let mut width_return: u32 = 0;
-            let mut height_return: u32 = 0;
-            let mut border_return: u32 = 0;
-            let mut depth_return: u32 = 0;
-            unsafe {
-                xlib::XGetGeometry(
-                    self.display,
-                    modeline,
-                    &mut root_return,
-                    &mut x_return,
-                    &mut y_return,
-                    &mut width_return,
-                    &mut height_return,
-                    &mut border_return,
-                    &mut depth_return,
-                );
-                xlib::XClearWindow(self.display, modeline);
-            }
-            let width = width_return as i32;
+    // modelines of the other monitors are dimmed
+    pub fn modeline_color(&self, mon_idx: usize) -> Color {
+        if mon_idx == self.current_monitor {
+            Color::Primary
+        } else {
+            Color::Inactive
+        }
+    }
 
-            // Dibujar izquierda
-            let mut x = margin;
-            for (i, text) in left_texts.iter().enumerate() {
-                if i > 0 {
-                    x += inter_padding;
-                }
-                let c_text = CString::new(text.as_str()).unwrap();
-                let len = c_text.to_bytes().len() as i32;
-                unsafe {
-                    xlib::XDrawString(self.display, modeline, self.gc, x, 10, c_text.as_ptr(), len);
-                }
-                x += len * char_width;
-            }
+    pub fn update_modelines(&mut self) {
+        if self.modelines.is_empty() {
+            return;
+        }
 
-            // Dibujar derecha
-            let right_start = width - margin - right_w;
-            x = right_start;
-            for (i, text) in right_texts.iter().enumerate() {
-                if i > 0 {
-                    x += inter_padding;
-                }
-                let c_text = CString::new(text.as_str()).unwrap();
-                let len = c_text.to_bytes().len() as i32;
-                unsafe {
-                    xlib::XDrawString(self.display, modeline, self.gc, x, 10, c_text.as_ptr(), len);
+        let margin = MODELINE_PADDING;
+        let mut clicks = HashMap::new();
+
+        for (&mon_idx, &modeline) in self.modelines.iter() {
+            let mut left: Vec<Segment> = vec![];
+            let mut center: Vec<Segment> = vec![];
+            let mut right: Vec<Segment> = vec![];
+
+            for (idx, module) in self.modules.iter().enumerate() {
+                let segments = self.module_segments(idx, mon_idx);
+                let side = match module.alignment {
+                    Alignment::Left => &mut left,
+                    Alignment::Center => &mut center,
+                    Alignment::Right => &mut right,
+                };
+                if !MODELINE_SEPARATOR.is_empty() && shows_text(side) && shows_text(&segments) {
+                    let color = self.modeline_color(mon_idx);
+                    side.push(Segment::plain(MODELINE_SEPARATOR.to_string(), color));
                 }
-                x += len * char_width;
+                side.extend(segments);
             }
 
-            // Dibujar centro
-            if !center_texts.is_empty() {
+            // the tray icons cover the right end
+            let width = match self.get_window_geometry(modeline) {
+                Some(attrs) => attrs.width - self.tray_width(mon_idx),
+                None => continue,
+            };
+
+            // the right side wins, then the center, the left takes what is left
+            let avail = width - margin * 2;
+            fit(self.backend.as_ref(), &mut right, avail);
+            let right_w = segments_width(self.backend.as_ref(), &right);
+            fit(self.backend.as_ref(), &mut center, avail - right_w);
+            let center_w = segments_width(self.backend.as_ref(), &center);
+            fit(self.backend.as_ref(), &mut left, avail - right_w - center_w);
+            let left_w = segments_width(self.backend.as_ref(), &left);
+
+            self.backend.clear(modeline);
+
+            let mut regions = Vec::new();
+            self.draw_segments(modeline, margin, &left, &mut regions);
+            self.draw_segments(modeline, width - margin - right_w, &right, &mut regions);
+            if !center.is_empty() {
                 let avail_start = margin + left_w;
-                let avail_end = width - margin - right_w;
-                let avail_w = avail_end - avail_start;
-                let center_start = avail_start + (avail_w - center_w) / 2;
-                x = center_start;
-                for (i, text) in center_texts.iter().enumerate() {
-                    if i > 0 {
-                        x += inter_padding;
-                    }
-                    let c_text = CString::new(text.as_str()).unwrap();
-                    let len = c_text.to_bytes().len() as i32;
-                    unsafe {
-                        xlib::XDrawString(
-                            self.display,
-                            modeline,
-                            self.gc,
-                            x,
-                            10,
-                            c_text.as_ptr(),
-                            len,
-                        );
-                    }
-                    x += len * char_width;
-                }
+                let avail_w = width - margin - right_w - avail_start;
+                let x = avail_start + (avail_w - center_w) / 2;
+                self.draw_segments(modeline, x, &center, &mut regions);
             }
+            clicks.insert(modeline, regions);
         }
+
+        self.modeline_clicks = clicks;
+    }
+
+    // draws `segments` from `x`, collecting the x ranges that react to clicks
+    fn draw_segments(
+        &self,
+        modeline: Window,
+        mut x: i32,
+        segments: &[Segment],
+        regions: &mut Vec<(i32, i32, Click)>,
+    ) {
+        let baseline = self.backend.text_baseline(MODELINE_HEIGHT);
+
+        for segment in segments {
+            let w = self.backend.text_width(&segment.text);
+            if let Some(bg) = &segment.bg {
+                let rect = Rect {
+                    x,
+                    y: 0,
+                    w: w.max(0) as u32,
+                    h: MODELINE_HEIGHT,
+                };
+                self.backend.fill_rect(modeline, rect, bg);
+            }
+            self.backend
+                .draw_text(modeline, x, baseline, &segment.text, &segment.fg);
+            if let Some(click) = segment.click {
+                regions.push((x, x + w, click));
+            }
+            x += w;
+        }
+    }
+
+    // monitor of `modeline` and what was drawn under `x`
+    pub fn modeline_click_at(&self, modeline: Window, x: i32) -> Option<(usize, Click)> {
+        let (&mon_idx, _) = self.modelines.iter().find(|&(_, &m)| m == modeline)?;
+        let click = self
+            .modeline_clicks
+            .get(&modeline)?
+            .iter()
+            .find(|&&(start, end, _)| x >= start && x < end)
+            .map(|&(_, _, click)| click)?;
+        Some((mon_idx, click))
+    }
+
+    // hides or brings back the modeline of the current monitor, the windows
+    // take its room while it is hidden
+    pub fn toggle_modeline(&mut self) {
+        let Some(mon) = self.monitors.get_mut(self.current_monitor) else {
+            return;
+        };
+        if mon.modeline.is_none() {
+            return;
+        }
+        mon.modeline_hidden = !mon.modeline_hidden;
+
+        self.create_modelines();
+        let _ = self.layout();
+        self.place_tray();
+    }
+
+    pub fn destroy_modelines(&mut self) {
+        for (_, modeline) in self.modelines.drain() {
+            self.backend.destroy_window(modeline);
+        }
+        self.modeline_clicks.clear();
     }
 
     pub fn spawn_modeline_timer(&self) {
-        if self.modelines.is_empty() {
+        self.backend
+            .spawn_timer(Duration::from_secs(MODELINE_UPDATE_TIME));
+    }
+}
+
+// longest start of `text` that fits in `max_width` once an ellipsis is added
+pub fn ellipsize(backend: &dyn Backend, text: &str, max_width: i32) -> String {
+    if backend.text_width(text) <= max_width {
+        return text.to_string();
+    }
+
+    let cuts: Vec<usize> = text.char_indices().map(|(i, _)| i).collect();
+    let kept = cuts.partition_point(|&end| {
+        backend.text_width(&format!("{}{}", &text[..end], ELLIPSIS)) <= max_width
+    });
+
+    match kept {
+        0 => String::new(),
+        n => format!("{}{}", text[..cuts[n - 1]].trim_end(), ELLIPSIS),
+    }
+}
+
+fn shows_text(segments: &[Segment]) -> bool {
+    segments.iter().any(|s| !s.text.is_empty())
+}
+
+fn segments_width(backend: &dyn Backend, segments: &[Segment]) -> i32 {
+    segments.iter().map(|s| backend.text_width(&s.text)).sum()
+}
+
+// shortens the widest of `segments` until all of them fit in `max_width`
+fn fit(backend: &dyn Backend, segments: &mut [Segment], max_width: i32) {
+    loop {
+        let widths: Vec<i32> = segments
+            .iter()
+            .map(|s| backend.text_width(&s.text))
+            .collect();
+        let overflow = widths.iter().sum::<i32>() - max_width;
+        if overflow <= 0 {
             return;
         }
-        let bar_win = self.modelines[0];
-        thread::spawn(move || {
-            let dpy = unsafe { xlib::XOpenDisplay(std::ptr::null()) };
-            if dpy.is_null() {
-                return;
-            }
-            let update_atom =
-                unsafe { xlib::XInternAtom(dpy, "MINIWM_UPDATE_BAR\0".as_ptr() as *const _, 0) };
-            loop {
-                thread::sleep(Duration::from_secs(MODELINE_UPDATE_TIME));
-                unsafe {
-                    let mut ev: xlib::XClientMessageEvent = std::mem::zeroed();
-                    ev.type_ = xlib::ClientMessage;
-                    ev.window = bar_win;
-                    ev.message_type = update_atom;
-                    ev.format = 32;
-                    let mut x_ev = xlib::XEvent::from(ev);
-                    xlib::XSendEvent(dpy, bar_win, 0, xlib::NoEventMask, &mut x_ev);
-                    xlib::XFlush(dpy);
-                }
-            }
-        });
+
+        let Some((idx, &widest)) = widths.iter().enumerate().max_by_key(|&(_, &w)| w) else {
+            return;
+        };
+        if widest == 0 {
+            return;
+        }
+        segments[idx].text = ellipsize(backend, &segments[idx].text, widest - overflow);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::koan::{
+        backend::fake::{Fake, SCREEN, SCREENS},
+        command::Reading,
+        config::GAPS,
+    };
+
+    // title and client count on the left, the cpu and the clock on the right
+    const TITLE: usize = 1;
+    const CPU: usize = 3;
+    static MODULES: &[Module] = &[
+        Module {
+            kind: ModuleType::Layout,
+            alignment: Alignment::Left,
+            fg: None,
+            bg: None,
+            clicks: &[],
+        },
+        Module {
+            kind: ModuleType::WindowTitle,
+            alignment: Alignment::Left,
+            fg: None,
+            bg: None,
+            clicks: &[],
+        },
+        Module {
+            kind: ModuleType::ClientCount,
+            alignment: Alignment::Left,
+            fg: None,
+            bg: None,
+            clicks: &[],
+        },
+        Module {
+            kind: ModuleType::Cpu {
+                format: "cpu {usage}% ",
+                high: 90,
+                interval: 2,
+            },
+            alignment: Alignment::Right,
+            fg: None,
+            bg: None,
+            clicks: &[],
+        },
+        Module {
+            kind: ModuleType::Clock,
+            alignment: Alignment::Right,
+            fg: None,
+            bg: None,
+            clicks: &[],
+        },
+    ];
+
+    #[test]
+    fn ellipsize_cuts_on_character_boundaries() {
+        let fake = Fake::new(&[]);
+
+        assert_eq!(ellipsize(&fake, "short", 40), "short");
+        assert_eq!(ellipsize(&fake, "日本語のタイトル", 40), "日本語の…");
+        assert_eq!(ellipsize(&fake, "two words", 40), "two…");
+        assert_eq!(ellipsize(&fake, "nothing fits", 4), "");
+    }
+
+    #[test]
+    fn the_widest_module_is_shortened_first() {
+        let fake = Fake::new(&[]);
+        let mut segments: Vec<Segment> = ["[]=".to_string(), "a".repeat(40), "[3]".to_string()]
+            .into_iter()
+            .map(|text| Segment::plain(text, Color::Primary))
+            .collect();
+
+        fit(&fake, &mut segments, 160);
+
+        assert_eq!(segments[0].text, "[]=");
+        assert_eq!(segments[2].text, "[3]");
+        assert!(segments[1].text.ends_with(ELLIPSIS));
+        assert!(segments_width(&fake, &segments) <= 160);
+    }
+
+    #[test]
+    fn long_titles_leave_room_for_the_clock() {
+        let screen = Rect {
+            x: 0,
+            y: 0,
+            w: 640,
+            h: 300,
+        };
+        let fake = Fake::new(&[screen]);
+        let mut wm = fake.koan();
+        wm.modules = MODULES;
+        wm.create_modelines();
+        let win = fake.create_window(100, 100);
+        fake.with_window(win, |w| w.title = Some("ü".repeat(100)));
+        wm.manage_window(win).unwrap();
+
+        let texts = fake.with_window(wm.modelines[&0], |w| w.texts.clone());
+        let (title_x, title) = texts.iter().find(|(_, t)| t.starts_with('ü')).unwrap();
+        let (clock_x, _) = texts.last().unwrap();
+        assert!(title.ends_with(ELLIPSIS));
+        assert!(title_x + fake.text_width(title) <= *clock_x);
+    }
+
+    #[test]
+    fn each_modeline_describes_its_own_monitor() {
+        let fake = Fake::new(&SCREENS);
+        let mut wm = fake.koan();
+        wm.modules = MODULES;
+        wm.create_modelines();
+        let a = fake.create_window(100, 100);
+        let b = fake.create_window(100, 100);
+        let c = fake.create_window(100, 100);
+        fake.with_window(a, |w| w.title = Some("left".into()));
+        fake.with_window(c, |w| w.title = Some("right".into()));
+        wm.manage_window(a).unwrap();
+        wm.manage_window(b).unwrap();
+        wm.focus_window(a);
+        wm.current_monitor = 1;
+        wm.manage_window(c).unwrap();
+
+        let texts = |mon_idx| -> Vec<String> {
+            fake.with_window(wm.modelines[&mon_idx], |w| {
+                w.texts.iter().map(|(_, t)| t.clone()).collect()
+            })
+        };
+        assert!(texts(0).contains(&"left".to_string()));
+        assert!(texts(0).contains(&"[2]".to_string()));
+        assert!(texts(1).contains(&"right".to_string()));
+        assert!(texts(1).contains(&"[1]".to_string()));
+
+        assert_eq!(wm.module_segments(TITLE, 0)[0].fg, Color::Inactive.hex());
+        assert_eq!(wm.module_segments(TITLE, 1)[0].fg, Color::Primary.hex());
+    }
+
+    #[test]
+    fn alerting_readings_are_drawn_as_errors() {
+        let fake = Fake::new(&[SCREEN]);
+        let mut wm = fake.koan();
+        wm.modules = MODULES;
+
+        wm.module_readings.insert(
+            CPU,
+            Reading {
+                text: "cpu 97% ".into(),
+                alert: true,
+            },
+        );
+
+        let segments = wm.module_segments(CPU, 0);
+        assert_eq!(segments[0].text, "cpu 97% ");
+        assert_eq!(segments[0].fg, Color::Error.hex());
+    }
+
+    #[test]
+    fn module_colors_replace_the_focused_modeline_colors() {
+        static MODULES: &[Module] = &[Module {
+            kind: ModuleType::Custom(|| "themed".to_string()),
+            alignment: Alignment::Left,
+            fg: Some("#000000"),
+            bg: Some("#88c0d0"),
+            clicks: &[],
+        }];
+        let fake = Fake::new(&SCREENS);
+        let mut wm = fake.koan();
+        wm.modules = MODULES;
+
+        let focused = &wm.module_segments(0, 0)[0];
+        assert_eq!(focused.fg, "#000000");
+        assert_eq!(focused.bg.as_deref(), Some("#88c0d0"));
+        let dimmed = &wm.module_segments(0, 1)[0];
+        assert_eq!(dimmed.fg, Color::Inactive.hex());
+        assert_eq!(dimmed.bg.as_deref(), Some("#88c0d0"));
+    }
+
+    #[test]
+    fn hidden_modelines_give_their_room_to_the_windows() {
+        let fake = Fake::new(&[SCREEN]);
+        let mut wm = fake.koan();
+        wm.create_modelines();
+        let win = fake.create_window(100, 100);
+        wm.manage_window(win).unwrap();
+        assert_eq!(fake.geometry(win).y, (MODELINE_HEIGHT + GAPS) as i32);
+
+        wm.run_action(Action::ToggleModeline).unwrap();
+        assert!(wm.modelines.is_empty());
+        assert_eq!(fake.geometry(win).y, GAPS as i32);
+
+        wm.run_action(Action::ToggleModeline).unwrap();
+        assert!(wm.modelines.contains_key(&0));
+        assert_eq!(fake.geometry(win).y, (MODELINE_HEIGHT + GAPS) as i32);
     }
 }
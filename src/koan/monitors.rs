@@ -1,92 +1,221 @@
-use std::{mem::zeroed, slice};
-
-use x11::{xinerama, xlib};
-
-use super::KoanWM;
+use super::{
+    backend::{Output, WindowAttributes},
+    config::{ModelinePosition, OutputConfig, DEFAULT_OUTPUT, MFACT, MONITOR_WRAP, NMASTER},
+    layouts::Rect,
+    window::Direction,
+    KoanWM, Window,
+};
 
 #[allow(dead_code)]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub struct Monitor {
+    pub name: String, // output name, stable across hotplugs unlike the index
+    pub primary: bool,
     pub x: i32,
     pub y: i32,
     pub width: i32,
     pub height: i32,
     pub index: usize,
+
+    pub layout: &'static str,
+    pub mfact: f32,
+    pub nmaster: usize,
+    pub gaps: u32,
+    pub modeline: Option<ModelinePosition>,
+    pub modeline_hidden: bool,   // by ToggleModeline, the position is kept
+    pub desktop: usize,          // index into DESKTOPS
+    pub focused: Option<Window>, // last client focused here
+}
+
+impl Monitor {
+    pub fn new(x: i32, y: i32, width: i32, height: i32, index: usize) -> Self {
+        Self {
+            name: String::new(),
+            primary: false,
+            x,
+            y,
+            width,
+            height,
+            index,
+            layout: DEFAULT_OUTPUT.layout,
+            mfact: MFACT,
+            nmaster: NMASTER,
+            gaps: DEFAULT_OUTPUT.gaps,
+            modeline: DEFAULT_OUTPUT.modeline,
+            modeline_hidden: false,
+            desktop: 0,
+            focused: None,
+        }
+    }
+
+    pub fn configure(&mut self, config: &OutputConfig) {
+        self.layout = config.layout;
+        self.gaps = config.gaps;
+        self.modeline = config.modeline;
+    }
+
+    // where the modeline is drawn, None when there is none or it is hidden
+    pub fn modeline_position(&self) -> Option<ModelinePosition> {
+        self.modeline.filter(|_| !self.modeline_hidden)
+    }
+}
+
+// "WxH+X+Y" geometries separated by commas
+pub fn parse_monitors(spec: &str) -> Option<Vec<Rect>> {
+    let monitors = spec
+        .split(',')
+        .map(|geometry| {
+            let (size, offset) = geometry.trim().split_once('+')?;
+            let (w, h) = size.split_once('x')?;
+            let (x, y) = offset.split_once('+')?;
+            let rect = Rect {
+                x: x.parse().ok()?,
+                y: y.parse().ok()?,
+                w: w.parse().ok()?,
+                h: h.parse().ok()?,
+            };
+            (rect.w > 0 && rect.h > 0).then_some(rect)
+        })
+        .collect::<Option<Vec<_>>>()?;
+    (!monitors.is_empty()).then_some(monitors)
 }
 
 impl KoanWM {
     pub fn update_monitors(&mut self) {
-        unsafe {
-            let mut num = 0i32;
-            let screens_ptr = xinerama::XineramaQueryScreens(self.display, &mut num);
+        let outputs = if self.virtual_monitors.is_empty() {
+            self.backend.monitors()
+        } else {
+            self.virtual_monitors
+                .iter()
+                .enumerate()
+                .map(|(i, &rect)| Output {
+                    name: format!("virtual-{}", i),
+                    rect,
+                    primary: i == 0,
+                })
+                .collect()
+        };
 
-            self.monitors.clear();
+        let old_monitors = std::mem::take(&mut self.monitors);
+        let mut old_trees = std::mem::take(&mut self.trees);
 
-            if screens_ptr.is_null() || num <= 0 {
-                let root = xlib::XDefaultRootWindow(self.display);
-                let mut root_attr: xlib::XWindowAttributes = zeroed();
-                xlib::XGetWindowAttributes(self.display, root, &mut root_attr);
-                self.monitors.push(Monitor {
-                    x: 0,
-                    y: 0,
-                    width: root_attr.width,
-                    height: root_attr.height,
-                    index: 0,
-                });
-            } else {
-                let screens = slice::from_raw_parts(screens_ptr, num as usize);
-                for (i, screen) in screens.iter().enumerate() {
-                    self.monitors.push(Monitor {
-                        x: screen.x_org as i32,
-                        y: screen.y_org as i32,
-                        width: screen.width as i32,
-                        height: screen.height as i32,
-                        index: i,
-                    });
+        for (i, output) in outputs.into_iter().enumerate() {
+            let r = output.rect;
+            let mut mon = Monitor::new(r.x, r.y, r.w as i32, r.h as i32, i);
+
+            // keep layout state of the outputs that are still connected
+            if let Some(old) = old_monitors.iter().find(|m| m.name == output.name) {
+                mon.layout = old.layout;
+                mon.mfact = old.mfact;
+                mon.nmaster = old.nmaster;
+                mon.gaps = old.gaps;
+                mon.modeline = old.modeline;
+                mon.modeline_hidden = old.modeline_hidden;
+                mon.desktop = old.desktop;
+                mon.focused = old.focused;
+                for ((_, desktop), tree) in old_trees.extract_if(|&(m, _), _| m == old.index) {
+                    self.trees.insert((i, desktop), tree);
                 }
-                xlib::XFree(screens_ptr as *mut _);
+            } else {
+                mon.configure(self.output_config(&output.name));
             }
 
-            let total_monitors = self.monitors.len();
-            for mon_idx in self.window_monitors.values_mut() {
-                if *mon_idx >= total_monitors {
-                    *mon_idx = 0;
+            mon.name = output.name;
+            mon.primary = output.primary;
+            self.monitors.push(mon);
+        }
+
+        // clients of a disconnected output go to the first monitor and
+        // remember where they came from, so they return when it is back
+        let mut moves = Vec::new();
+        for (&window, &mon_idx) in self.window_monitors.iter() {
+            let home = self
+                .window_homes
+                .get(&window)
+                .or(old_monitors.get(mon_idx).map(|m| &m.name));
+
+            match home.and_then(|name| self.monitor_index(name)) {
+                Some(idx) => moves.push((window, idx, None)),
+                None => moves.push((window, 0, home.cloned())),
+            }
+        }
+        for (window, idx, home) in moves {
+            self.window_monitors.insert(window, idx);
+            match home {
+                Some(name) => {
+                    // otherwise it stays hidden on a desktop the monitor doesn't show
+                    if let Some(desktop) = self.monitors.get(idx).map(|m| m.desktop) {
+                        self.set_desktop(window, desktop);
+                    }
+                    self.window_homes.insert(window, name);
+                }
+                None => {
+                    self.window_homes.remove(&window);
                 }
             }
+        }
 
-            if self.current_monitor >= total_monitors {
-                self.current_monitor = 0;
-            }
+        self.current_monitor = old_monitors
+            .get(self.current_monitor)
+            .and_then(|m| self.monitor_index(&m.name))
+            .unwrap_or(0);
+
+        // recreated on the next layout, their monitor index may have changed
+        for (_, bar) in self.tab_bars.drain() {
+            self.backend.destroy_window(bar);
         }
     }
 
-    pub fn focus_monitor(&mut self, index: usize) {
-        let mon = self.monitors[index];
-        unsafe {
-            xlib::XWarpPointer(
-                self.display,
-                0, // None
-                xlib::XDefaultRootWindow(self.display),
-                0,
-                0,
-                0,
-                0,
-                mon.x + (mon.width / 2),
-                mon.y + (mon.height / 2),
-            );
-            xlib::XFlush(self.display);
-        }
+    pub fn monitor_index(&self, name: &str) -> Option<usize> {
+        self.monitors.iter().position(|m| m.name == name)
+    }
 
-        let last_client = self
-            .clients
+    pub fn output_config(&self, name: &str) -> &'static OutputConfig {
+        self.output_configs
             .iter()
-            .filter(|&c| self.window_monitors.get(&c.window) == Some(&index))
-            .last();
+            .find(|c| c.name == name)
+            .unwrap_or(&DEFAULT_OUTPUT)
+    }
 
-        if let Some(client) = last_client {
-            self.focus_window(client.window);
-        } else {
-            self.focused = None;
+    // connected monitor the application of `window` is configured to open on
+    pub fn app_monitor(&self, window: Window) -> Option<usize> {
+        let class = self.backend.window_class(window);
+        self.output_configs
+            .iter()
+            .filter(|c| c.apps.iter().any(|app| class.iter().any(|n| n == app)))
+            .find_map(|c| self.monitor_index(c.name))
+    }
+
+    // client the modeline of `mon_idx` is about, the last one focused there
+    // while it is still shown
+    pub fn monitor_focus(&self, mon_idx: usize) -> Option<Window> {
+        self.monitors
+            .get(mon_idx)?
+            .focused
+            .filter(|&w| self.is_shown(w, mon_idx))
+    }
+
+    pub fn focus_monitor(&mut self, index: usize) {
+        self.warp_to_monitor(index);
+        self.focus_last_shown(index);
+        self.update_current_desktop();
+    }
+
+    fn warp_to_monitor(&self, index: usize) {
+        let mon = &self.monitors[index];
+        self.backend
+            .warp_pointer(mon.x + (mon.width / 2), mon.y + (mon.height / 2));
+    }
+
+    // index order, wrapping around at the ends when MONITOR_WRAP is set
+    fn step_monitor(&self, from: usize, next: bool) -> usize {
+        let count = self.monitors.len();
+        match (next, MONITOR_WRAP) {
+            (true, _) if from + 1 < count => from + 1,
+            (false, _) if from > 0 => from - 1,
+            (true, true) => 0,
+            (false, true) => count - 1,
+            _ => from,
         }
     }
 
@@ -95,10 +224,7 @@ impl KoanWM {
             return;
         }
 
-        if self.current_monitor + 1 < self.monitors.len() {
-            self.current_monitor += 1;
-        }
-
+        self.current_monitor = self.step_monitor(self.current_monitor, true);
         self.focus_monitor(self.current_monitor);
     }
 
@@ -107,48 +233,465 @@ impl KoanWM {
             return;
         }
 
-        if self.current_monitor > 0 {
-            self.current_monitor -= 1;
-        }
-
+        self.current_monitor = self.step_monitor(self.current_monitor, false);
         self.focus_monitor(self.current_monitor);
     }
 
+    pub fn monitor_towards(&mut self, dir: Direction) {
+        if let Some(idx) = monitor_towards(&self.monitors, self.current_monitor, dir, MONITOR_WRAP)
+        {
+            self.current_monitor = idx;
+            self.focus_monitor(idx);
+        }
+    }
+
     pub fn move_window_next_monitor(&mut self) {
-        let Some(focused) = self.focused else {
-            return;
-        };
-        let monitor_count = self.monitors.len();
-        if monitor_count == 0 {
-            return;
+        if let Some(&idx) = self.focused.and_then(|w| self.window_monitors.get(&w)) {
+            self.move_window_to_monitor(self.step_monitor(idx, true));
         }
+    }
 
-        if let Some(idx) = self.window_monitors.get_mut(&focused) {
-            if *idx + 1 < monitor_count {
-                let new_idx = *idx + 1;
-                *idx = new_idx;
-                self.focus_monitor(new_idx);
-            }
+    pub fn move_window_prev_monitor(&mut self) {
+        if let Some(&idx) = self.focused.and_then(|w| self.window_monitors.get(&w)) {
+            self.move_window_to_monitor(self.step_monitor(idx, false));
         }
+    }
 
-        let _ = self.layout();
+    pub fn move_window_towards(&mut self, dir: Direction) {
+        if let Some(&idx) = self.focused.and_then(|w| self.window_monitors.get(&w))
+            && let Some(target) = monitor_towards(&self.monitors, idx, dir, MONITOR_WRAP)
+        {
+            self.move_window_to_monitor(target);
+        }
     }
 
-    pub fn move_window_prev_monitor(&mut self) {
+    pub fn move_window_to_monitor(&mut self, target: usize) {
         let Some(focused) = self.focused else { return };
-        let monitor_count = self.monitors.len();
-        if monitor_count == 0 {
+        let Some(&from) = self.window_monitors.get(&focused) else {
+            return;
+        };
+        if from == target {
             return;
         }
 
-        if let Some(idx) = self.window_monitors.get_mut(&focused) {
-            if *idx > 0 {
-                let new_idx = *idx - 1;
-                *idx = new_idx;
-                self.focus_monitor(new_idx);
-            }
+        self.window_monitors.insert(focused, target);
+        // moved on purpose, stays here when its old output returns
+        self.window_homes.remove(&focused);
+        self.set_desktop(focused, self.monitors[target].desktop);
+
+        if self.focused_client().is_some_and(|c| c.is_float)
+            && let Some(attr) = self.get_window_geometry(focused)
+        {
+            let (x, y) = relative_position(&self.monitors[from], &self.monitors[target], attr);
+            self.backend.move_window(focused, x, y);
         }
 
+        self.current_monitor = target;
+        self.warp_to_monitor(target);
         let _ = self.layout();
+        self.focus_window(focused);
+    }
+}
+
+// closest monitor from `from` in `dir` by position, or the farthest one on the
+// other side when there is none and `wrap` is set
+pub fn monitor_towards(
+    monitors: &[Monitor],
+    from: usize,
+    dir: Direction,
+    wrap: bool,
+) -> Option<usize> {
+    let cur = monitors.get(from)?;
+
+    // space between the `dir` edge of `cur` and `m`, negative when `m` is not
+    // entirely on that side
+    let gap = |m: &Monitor, dir: Direction| match dir {
+        Direction::Right => m.x - (cur.x + cur.width),
+        Direction::Left => cur.x - (m.x + m.width),
+        Direction::Down => m.y - (cur.y + cur.height),
+        Direction::Up => cur.y - (m.y + m.height),
+    };
+    // sharing a row or column beats being closer diagonally
+    let horizontal = matches!(dir, Direction::Left | Direction::Right);
+    let aligned = |m: &Monitor| match horizontal {
+        true => m.y < cur.y + cur.height && cur.y < m.y + m.height,
+        false => m.x < cur.x + cur.width && cur.x < m.x + m.width,
+    };
+    let across = |m: &Monitor| match horizontal {
+        true => ((m.y + m.height / 2) - (cur.y + cur.height / 2)).abs(),
+        false => ((m.x + m.width / 2) - (cur.x + cur.width / 2)).abs(),
+    };
+    let closest = |dir: Direction, farthest: bool| {
+        monitors
+            .iter()
+            .enumerate()
+            .filter(|&(i, m)| i != from && gap(m, dir) >= 0)
+            .min_by_key(|&(_, m)| {
+                let distance = if farthest { -gap(m, dir) } else { gap(m, dir) };
+                (!aligned(m), distance, across(m))
+            })
+            .map(|(i, _)| i)
+    };
+
+    closest(dir, false).or_else(|| wrap.then(|| closest(dir.opposite(), true)).flatten())
+}
+
+// where a floating window at `rect` on `from` lands on `to`, at the same
+// relative spot and still inside it
+pub fn relative_position(from: &Monitor, to: &Monitor, rect: WindowAttributes) -> (i32, i32) {
+    let scale = |pos: i32, from_start: i32, from_len: i32, to_start: i32, to_len: i32, len: i32| {
+        let rel = (pos - from_start) as i64 * to_len as i64 / from_len.max(1) as i64;
+        (to_start + rel as i32)
+            .min(to_start + to_len - len)
+            .max(to_start)
+    };
+
+    (
+        scale(rect.x, from.x, from.width, to.x, to.width, rect.width),
+        scale(rect.y, from.y, from.height, to.y, to.height, rect.height),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::koan::backend::{
+        fake::{Fake, SCREENS},
+        Event,
+    };
+
+    #[test]
+    fn monitors_come_from_the_backend() {
+        let fake = Fake::new(&SCREENS);
+        let wm = fake.koan();
+
+        assert_eq!(wm.monitors.len(), 2);
+        assert_eq!(wm.monitors[1].x, 1920);
+        assert_eq!(wm.monitors[1].height, 1024);
+    }
+
+    #[test]
+    fn layout_state_survives_a_monitor_update() {
+        let fake = Fake::new(&SCREENS);
+        let mut wm = fake.koan();
+        wm.monitors[1].layout = "tile";
+        wm.monitors[1].nmaster = 3;
+
+        wm.update_monitors();
+
+        assert_eq!(wm.monitors[1].layout, "tile");
+        assert_eq!(wm.monitors[1].nmaster, 3);
+    }
+
+    #[test]
+    fn moving_a_window_to_the_next_monitor() {
+        let fake = Fake::new(&SCREENS);
+        let mut wm = fake.koan();
+        let win = fake.create_window(300, 200);
+        wm.manage_window(win).unwrap();
+
+        wm.move_window_next_monitor();
+
+        assert_eq!(wm.window_monitors[&win], 1);
+        assert_eq!(wm.current_monitor, 1);
+        assert_eq!(wm.focused, Some(win));
+        assert_eq!(fake.state.borrow().pointer, (1920 + 640, 512));
+
+        let geom = fake.geometry(win);
+        assert!(geom.x >= 1920);
+        assert!(geom.x + geom.w as i32 <= 1920 + 1280);
+
+        // there is no monitor after the last one
+        wm.move_window_next_monitor();
+        assert_eq!(wm.window_monitors[&win], 1);
+
+        wm.move_window_prev_monitor();
+        assert_eq!(wm.window_monitors[&win], 0);
+        assert!(fake.geometry(win).x < 1920);
+    }
+
+    #[test]
+    fn focusing_an_empty_monitor_clears_focus() {
+        let fake = Fake::new(&SCREENS);
+        let mut wm = fake.koan();
+        let win = fake.create_window(300, 200);
+        wm.manage_window(win).unwrap();
+
+        wm.monitor_next();
+
+        assert_eq!(wm.current_monitor, 1);
+        assert_eq!(wm.focused, None);
+    }
+
+    #[test]
+    fn parses_monitor_geometries() {
+        assert_eq!(
+            parse_monitors("800x600+0+0, 1120x600+800+-10"),
+            Some(vec![
+                Rect {
+                    x: 0,
+                    y: 0,
+                    w: 800,
+                    h: 600
+                },
+                Rect {
+                    x: 800,
+                    y: -10,
+                    w: 1120,
+                    h: 600
+                },
+            ])
+        );
+
+        for bad in [
+            "",
+            "800x600",
+            "800x600+0",
+            "0x600+0+0",
+            "axb+0+0",
+            "800x600+0+0,",
+        ] {
+            assert_eq!(parse_monitors(bad), None, "{bad:?}");
+        }
+    }
+
+    #[test]
+    fn virtual_monitors_replace_the_outputs() {
+        let fake = Fake::new(&SCREENS[..1]);
+        let mut wm = fake.koan();
+        wm.virtual_monitors = parse_monitors("960x1080+0+0,960x1080+960+0").unwrap();
+        wm.update_monitors();
+
+        assert_eq!(wm.monitors.len(), 2);
+        assert_eq!(wm.monitors[1].x, 960);
+
+        let win = fake.create_window(300, 200);
+        wm.manage_window(win).unwrap();
+        wm.move_window_next_monitor();
+
+        let geom = fake.geometry(win);
+        assert!(geom.x >= 960);
+        assert!(geom.x + geom.w as i32 <= 1920);
+    }
+
+    #[test]
+    fn layout_state_follows_the_output_name() {
+        let fake = Fake::new(&SCREENS);
+        let mut wm = fake.koan();
+        wm.monitors[1].layout = "tile";
+
+        // same outputs, reported in a different order
+        fake.set_outputs(&[("OUT-1", SCREENS[1]), ("OUT-0", SCREENS[0])]);
+        wm.update_monitors();
+
+        assert_eq!(wm.monitors[0].name, "OUT-1");
+        assert_eq!(wm.monitors[0].layout, "tile");
+        assert_ne!(wm.monitors[1].layout, "tile");
+    }
+
+    #[test]
+    fn clients_return_when_their_output_is_reconnected() {
+        let fake = Fake::new(&SCREENS);
+        let mut wm = fake.koan();
+        let win = fake.create_window(300, 200);
+        wm.manage_window(win).unwrap();
+        wm.move_window_next_monitor();
+
+        fake.set_outputs(&[("OUT-0", SCREENS[0])]);
+        wm.update_monitors();
+        wm.layout().unwrap();
+
+        assert_eq!(wm.window_monitors[&win], 0);
+        assert_eq!(wm.current_monitor, 0);
+        assert!(fake.geometry(win).x < 1920);
+
+        fake.set_outputs(&[("OUT-0", SCREENS[0]), ("OUT-1", SCREENS[1])]);
+        wm.update_monitors();
+        wm.layout().unwrap();
+
+        assert_eq!(wm.window_monitors[&win], 1);
+        assert!(fake.geometry(win).x >= 1920);
+        assert!(wm.window_homes.is_empty());
+    }
+
+    #[test]
+    fn rescued_clients_show_up_on_the_first_monitor() {
+        let fake = Fake::new(&SCREENS);
+        let mut wm = fake.koan();
+        wm.current_monitor = 1;
+        wm.view_desktop(2);
+        let win = fake.create_window(300, 200);
+        wm.manage_window(win).unwrap();
+        assert_eq!(wm.window_desktops[&win], 2);
+
+        fake.set_outputs(&[("OUT-0", SCREENS[0])]);
+        fake.push(Event::ScreenChange);
+        wm.run().unwrap();
+
+        assert_eq!(wm.window_monitors[&win], 0);
+        assert_eq!(wm.window_desktops[&win], wm.monitors[0].desktop);
+        assert!(!wm.hidden.contains(&win));
+        assert!(fake.with_window(win, |w| w.mapped));
+        assert!(fake.geometry(win).x < 1920);
+    }
+
+    #[test]
+    fn moved_clients_forget_their_disconnected_output() {
+        let fake = Fake::new(&SCREENS);
+        let mut wm = fake.koan();
+        let win = fake.create_window(300, 200);
+        wm.manage_window(win).unwrap();
+        wm.move_window_next_monitor();
+
+        fake.set_outputs(&[("OUT-0", SCREENS[0]), ("OUT-2", SCREENS[1])]);
+        wm.update_monitors();
+        wm.move_window_next_monitor();
+
+        fake.set_outputs(&[("OUT-0", SCREENS[0]), ("OUT-1", SCREENS[1])]);
+        wm.update_monitors();
+
+        // the window was on OUT-2, which is gone now, so it goes to the first
+        assert_eq!(wm.window_monitors[&win], 0);
+        assert_eq!(wm.window_homes[&win], "OUT-2");
+    }
+
+    static CONFIGS: &[OutputConfig] = &[OutputConfig {
+        name: "HDMI-1",
+        layout: "tile",
+        gaps: 0,
+        modeline: None,
+        apps: &["firefox"],
+    }];
+
+    fn configured(outputs: &[(&str, Rect)]) -> (Fake, KoanWM) {
+        let fake = Fake::new(&[]);
+        fake.set_outputs(outputs);
+        let mut wm = KoanWM::with_backend(Box::new(fake.clone()));
+        wm.output_configs = CONFIGS;
+        wm.update_monitors();
+        (fake, wm)
+    }
+
+    #[test]
+    fn outputs_are_configured_by_name() {
+        let (_, wm) = configured(&[("HDMI-1", SCREENS[0]), ("eDP-1", SCREENS[1])]);
+        assert_eq!(wm.monitors[0].layout, "tile");
+        assert_eq!(wm.monitors[0].gaps, 0);
+        assert_eq!(wm.monitors[0].modeline, None);
+        assert_eq!(wm.monitors[1].layout, DEFAULT_OUTPUT.layout);
+
+        // plug order does not matter
+        let (_, wm) = configured(&[("eDP-1", SCREENS[0]), ("HDMI-1", SCREENS[1])]);
+        assert_eq!(wm.monitors[1].layout, "tile");
+        assert_eq!(wm.monitors[0].layout, DEFAULT_OUTPUT.layout);
+    }
+
+    #[test]
+    fn applications_open_on_their_output() {
+        let (fake, mut wm) = configured(&[("eDP-1", SCREENS[0]), ("HDMI-1", SCREENS[1])]);
+
+        let browser = fake.create_window(300, 200);
+        fake.with_window(browser, |w| {
+            w.class = vec!["Navigator".into(), "firefox".into()]
+        });
+        wm.manage_window(browser).unwrap();
+        assert_eq!(wm.window_monitors[&browser], 1);
+
+        let term = fake.create_window(300, 200);
+        wm.manage_window(term).unwrap();
+        assert_eq!(wm.window_monitors[&term], 1);
+
+        // without the output connected it opens on the current monitor
+        let (fake, mut wm) = configured(&[("eDP-1", SCREENS[0])]);
+        let browser = fake.create_window(300, 200);
+        fake.with_window(browser, |w| w.class = vec!["firefox".into()]);
+        wm.manage_window(browser).unwrap();
+        assert_eq!(wm.window_monitors[&browser], 0);
+    }
+
+    #[test]
+    fn hidden_modelines_are_not_created() {
+        let (_, mut wm) = configured(&[("eDP-1", SCREENS[0]), ("HDMI-1", SCREENS[1])]);
+        wm.create_modelines();
+
+        assert!(wm.modelines.contains_key(&0));
+        assert!(!wm.modelines.contains_key(&1));
+    }
+
+    #[test]
+    fn directions_follow_the_monitor_positions() {
+        // A B
+        // C
+        let monitors = [
+            Monitor::new(0, 0, 1920, 1080, 0),
+            Monitor::new(1920, 0, 1280, 1024, 1),
+            Monitor::new(0, 1080, 1920, 1080, 2),
+        ];
+        let towards = |from, dir| monitor_towards(&monitors, from, dir, false);
+
+        assert_eq!(towards(0, Direction::Right), Some(1));
+        assert_eq!(towards(0, Direction::Down), Some(2));
+        assert_eq!(towards(0, Direction::Left), None);
+        assert_eq!(towards(1, Direction::Left), Some(0));
+        assert_eq!(towards(2, Direction::Up), Some(0));
+        assert_eq!(towards(2, Direction::Right), Some(1));
+        assert_eq!(towards(1, Direction::Down), Some(2));
+    }
+
+    #[test]
+    fn directions_wrap_around_to_the_farthest_monitor() {
+        let monitors = [
+            Monitor::new(0, 0, 800, 600, 0),
+            Monitor::new(800, 0, 800, 600, 1),
+            Monitor::new(1600, 0, 800, 600, 2),
+        ];
+
+        assert_eq!(
+            monitor_towards(&monitors, 2, Direction::Right, true),
+            Some(0)
+        );
+        assert_eq!(
+            monitor_towards(&monitors, 0, Direction::Left, true),
+            Some(2)
+        );
+        assert_eq!(monitor_towards(&monitors, 0, Direction::Up, true), None);
+    }
+
+    #[test]
+    fn floating_windows_keep_their_relative_position() {
+        let fake = Fake::new(&SCREENS);
+        let mut wm = fake.koan();
+        let win = fake.create_window(300, 200);
+        wm.manage_window(win).unwrap();
+        wm.toggle_float();
+        let rect = Rect {
+            x: 960,
+            y: 0,
+            w: 300,
+            h: 200,
+        };
+        wm.backend.move_resize(win, rect);
+
+        wm.move_window_towards(Direction::Right);
+
+        assert_eq!(wm.window_monitors[&win], 1);
+        assert_eq!(wm.focused, Some(win));
+        let geom = fake.geometry(win);
+        assert_eq!((geom.x, geom.y), (1920 + 640, 0));
+    }
+
+    #[test]
+    fn relative_positions_stay_inside_the_monitor() {
+        let big = Monitor::new(0, 0, 1920, 1080, 0);
+        let small = Monitor::new(1920, 0, 800, 600, 1);
+        let window = WindowAttributes {
+            x: 1800,
+            y: 1000,
+            width: 400,
+            height: 300,
+            ..Default::default()
+        };
+
+        assert_eq!(relative_position(&big, &small, window), (1920 + 400, 300));
     }
 }
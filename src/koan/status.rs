@@ -0,0 +1,144 @@
+use super::{modeline::Segment, KoanWM};
+
+impl KoanWM {
+    // the root window name, as set by `xsetroot -name` from slstatus or a
+    // shell loop
+    pub fn root_name_segments(&self, mon_idx: usize) -> Vec<Segment> {
+        let name = self
+            .backend
+            .window_title(self.backend.root())
+            .unwrap_or_default();
+        parse_status2d(&name, self.modeline_color(mon_idx).hex())
+    }
+}
+
+// splits dwm status2d text on its color escapes: ^c#rrggbb^ sets the text
+// color, ^b#rrggbb^ the background and ^d^ goes back to the defaults. The
+// other escapes (^r, ^f) draw shapes and are dropped, so are colors that are
+// not #rgb or #rrggbb, the text keeps the previous color instead of vanishing
+pub fn parse_status2d(text: &str, default_fg: &str) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    let mut fg = default_fg.to_string();
+    let mut bg = None;
+    let mut rest = text;
+
+    while !rest.is_empty() {
+        // a caret without a closing one is plain text
+        let escape = rest.find('^').and_then(|start| {
+            let len = rest[start + 1..].find('^')?;
+            Some((start, &rest[start + 1..start + 1 + len]))
+        });
+        let (shown, next) = match escape {
+            Some((start, escape)) => (&rest[..start], start + escape.len() + 2),
+            None => (rest, rest.len()),
+        };
+
+        if !shown.is_empty() {
+            segments.push(Segment {
+                text: shown.to_string(),
+                fg: fg.clone(),
+                bg: bg.clone(),
+                click: None,
+            });
+        }
+
+        if let Some((_, escape)) = escape {
+            match escape.split_at(escape.len().min(1)) {
+                ("c", color) if is_hex_color(color) => fg = color.to_string(),
+                ("b", color) if is_hex_color(color) => bg = Some(color.to_string()),
+                ("d", _) => {
+                    fg = default_fg.to_string();
+                    bg = None;
+                }
+                _ => {}
+            }
+        }
+        rest = &rest[next..];
+    }
+    segments
+}
+
+fn is_hex_color(color: &str) -> bool {
+    color
+        .strip_prefix('#')
+        .is_some_and(|hex| matches!(hex.len(), 3 | 6) && hex.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::koan::{
+        backend::{
+            fake::{Fake, SCREEN},
+            Event,
+        },
+        config::Color,
+        modeline::{Alignment, Module, ModuleType},
+    };
+
+    fn parts(segments: &[Segment]) -> Vec<(&str, &str, Option<&str>)> {
+        segments
+            .iter()
+            .map(|s| (s.text.as_str(), s.fg.as_str(), s.bg.as_deref()))
+            .collect()
+    }
+
+    #[test]
+    fn status2d_escapes_color_the_text() {
+        let segments = parse_status2d(
+            "vol 40% ^c#ff0000^^b#000000^bat 5%^d^ 12:00^r0,0,5,5^",
+            "#00ff00",
+        );
+
+        assert_eq!(
+            parts(&segments),
+            [
+                ("vol 40% ", "#00ff00", None),
+                ("bat 5%", "#ff0000", Some("#000000")),
+                (" 12:00", "#00ff00", None),
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_colors_keep_the_text() {
+        let segments = parse_status2d("^c#zzzzzz^^b#12^cpu ^c#f00^hot", "#00ff00");
+        assert_eq!(
+            parts(&segments),
+            [("cpu ", "#00ff00", None), ("hot", "#f00", None)]
+        );
+    }
+
+    #[test]
+    fn lone_carets_are_plain_text() {
+        let segments = parse_status2d("^c#ff0000^cpu ^ 90%", "#00ff00");
+        assert_eq!(parts(&segments), [("cpu ^ 90%", "#ff0000", None)]);
+        assert!(parse_status2d("", "#00ff00").is_empty());
+    }
+
+    #[test]
+    fn xsetroot_updates_the_modeline() {
+        let fake = Fake::new(&[SCREEN]);
+        let mut wm = fake.koan();
+        static MODULES: &[Module] = &[Module {
+            kind: ModuleType::RootName,
+            alignment: Alignment::Right,
+            fg: None,
+            bg: None,
+            clicks: &[],
+        }];
+        wm.modules = MODULES;
+        wm.create_modelines();
+
+        fake.with_window(1, |w| w.title = Some("^c#ff5555^low battery".into()));
+        fake.push(Event::PropertyNotify {
+            window: 1,
+            atom: wm.wm_name,
+        });
+        wm.run().unwrap();
+
+        let texts = fake.with_window(wm.modelines[&0], |w| w.texts.clone());
+        assert!(texts.iter().any(|(_, t)| t == "low battery"));
+        assert_eq!(wm.module_segments(0, 0)[0].fg, Color::Error.hex());
+    }
+}
@@ -0,0 +1,294 @@
+use std::{fs, path::Path, process::Command, time::Instant};
+
+use super::{command::Reading, modeline::ModuleType};
+
+const POWER_SUPPLY: &str = "/sys/class/power_supply";
+
+pub type Sampler = Box<dyn FnMut() -> Reading + Send>;
+
+// interval and reading function of the system info modules, None for the
+// other kinds
+pub fn sampler(kind: &ModuleType) -> Option<(u64, Sampler)> {
+    let sampler: Sampler = match *kind {
+        ModuleType::Battery { format, low, .. } => Box::new(move || battery(format, low)),
+        ModuleType::Cpu { format, high, .. } => {
+            let mut last = None;
+            Box::new(move || {
+                let Some(sample) = fs::read_to_string("/proc/stat")
+                    .ok()
+                    .and_then(|s| parse_cpu(&s))
+                else {
+                    return Reading::default();
+                };
+                let usage = last.map_or(0, |prev| cpu_usage(prev, sample));
+                last = Some(sample);
+                Reading {
+                    text: fill(format, &[("usage", usage.to_string())]),
+                    alert: usage >= high,
+                }
+            })
+        }
+        ModuleType::Memory { format, high, .. } => Box::new(move || {
+            let Some((total, available)) = fs::read_to_string("/proc/meminfo")
+                .ok()
+                .and_then(|s| parse_meminfo(&s))
+            else {
+                return Reading::default();
+            };
+            let used = total.saturating_sub(available);
+            let percent = (used * 100 / total.max(1)) as u32;
+            Reading {
+                text: fill(
+                    format,
+                    &[
+                        ("used", human(used * 1024)),
+                        ("total", human(total * 1024)),
+                        ("percent", percent.to_string()),
+                    ],
+                ),
+                alert: percent >= high,
+            }
+        }),
+        ModuleType::Network {
+            interface, format, ..
+        } => {
+            let mut last: Option<(Instant, (u64, u64))> = None;
+            Box::new(move || {
+                let Some(bytes) = fs::read_to_string("/proc/net/dev")
+                    .ok()
+                    .and_then(|s| parse_net_dev(&s, interface))
+                else {
+                    return Reading::default();
+                };
+                let now = Instant::now();
+                let (down, up) = match last {
+                    Some((then, (rx, tx))) => {
+                        let secs = now.duration_since(then).as_secs_f64().max(1.0);
+                        let rate =
+                            |now: u64, then: u64| (now.saturating_sub(then) as f64 / secs) as u64;
+                        (rate(bytes.0, rx), rate(bytes.1, tx))
+                    }
+                    None => (0, 0),
+                };
+                last = Some((now, bytes));
+                Reading::plain(fill(format, &[("down", human(down)), ("up", human(up))]))
+            })
+        }
+        ModuleType::Volume {
+            control,
+            format,
+            muted,
+            ..
+        } => Box::new(move || {
+            let output = Command::new("amixer").args(["get", control]).output();
+            let Some((volume, on)) = output
+                .ok()
+                .and_then(|o| parse_amixer(&String::from_utf8_lossy(&o.stdout)))
+            else {
+                return Reading::default();
+            };
+            let text = if on { format } else { muted };
+            Reading::plain(fill(text, &[("volume", volume.to_string())]))
+        }),
+        _ => return None,
+    };
+
+    let interval = match *kind {
+        ModuleType::Battery { interval, .. }
+        | ModuleType::Cpu { interval, .. }
+        | ModuleType::Memory { interval, .. }
+        | ModuleType::Network { interval, .. }
+        | ModuleType::Volume { interval, .. } => interval,
+        _ => return None,
+    };
+    Some((interval, sampler))
+}
+
+// replaces every {key} of `format`
+fn fill(format: &str, values: &[(&str, String)]) -> String {
+    values
+        .iter()
+        .fold(format.to_string(), |text, (key, value)| {
+            text.replace(&format!("{{{}}}", key), value)
+        })
+}
+
+// 1024 based, one decimal below 10
+fn human(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "K", "M", "G", "T"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 || value >= 10.0 {
+        format!("{:.0}{}", value, UNITS[unit])
+    } else {
+        format!("{:.1}{}", value, UNITS[unit])
+    }
+}
+
+// first battery, empty on machines without one
+fn battery(format: &str, low: u32) -> Reading {
+    let Ok(entries) = fs::read_dir(POWER_SUPPLY) else {
+        return Reading::default();
+    };
+    let mut supplies: Vec<_> = entries.filter_map(|e| e.ok()).map(|e| e.path()).collect();
+    supplies.sort();
+
+    let read = |dir: &Path, file: &str| {
+        fs::read_to_string(dir.join(file))
+            .map(|s| s.trim().to_string())
+            .unwrap_or_default()
+    };
+    let Some(dir) = supplies.iter().find(|dir| read(dir, "type") == "Battery") else {
+        return Reading::default();
+    };
+    let Ok(capacity) = read(dir, "capacity").parse::<u32>() else {
+        return Reading::default();
+    };
+    let status = read(dir, "status");
+
+    Reading {
+        text: fill(
+            format,
+            &[
+                ("capacity", capacity.to_string()),
+                ("status", status.clone()),
+            ],
+        ),
+        alert: capacity <= low && status == "Discharging",
+    }
+}
+
+// idle and total jiffies of the "cpu" line. guest and guest_nice, the last
+// two fields, are already counted in user and nice
+fn parse_cpu(stat: &str) -> Option<(u64, u64)> {
+    let line = stat.lines().find(|l| l.starts_with("cpu "))?;
+    let fields: Vec<u64> = line
+        .split_whitespace()
+        .skip(1)
+        .map(|f| f.parse().ok())
+        .collect::<Option<_>>()?;
+    // idle and iowait
+    let idle = fields.get(3)? + fields.get(4).unwrap_or(&0);
+    Some((idle, fields.iter().take(8).sum()))
+}
+
+fn cpu_usage(prev: (u64, u64), cur: (u64, u64)) -> u32 {
+    let total = cur.1.saturating_sub(prev.1);
+    let idle = cur.0.saturating_sub(prev.0);
+    match total {
+        0 => 0,
+        total => (total.saturating_sub(idle) * 100 / total) as u32,
+    }
+}
+
+// MemTotal and MemAvailable, in kB
+fn parse_meminfo(meminfo: &str) -> Option<(u64, u64)> {
+    let field = |name: &str| {
+        meminfo
+            .lines()
+            .find_map(|l| l.strip_prefix(name)?.strip_prefix(':'))?
+            .split_whitespace()
+            .next()?
+            .parse::<u64>()
+            .ok()
+    };
+    Some((field("MemTotal")?, field("MemAvailable")?))
+}
+
+// received and transmitted bytes of `interface`, or of all of them but the
+// loopback when it is empty
+fn parse_net_dev(dev: &str, interface: &str) -> Option<(u64, u64)> {
+    let counters: Vec<(u64, u64)> = dev
+        .lines()
+        .filter_map(|l| {
+            let (name, stats) = l.split_once(':')?;
+            let name = name.trim();
+            let wanted = match interface {
+                "" => name != "lo",
+                _ => name == interface,
+            };
+            if !wanted {
+                return None;
+            }
+            let stats: Vec<&str> = stats.split_whitespace().collect();
+            Some((stats.first()?.parse().ok()?, stats.get(8)?.parse().ok()?))
+        })
+        .collect();
+
+    (!counters.is_empty()).then(|| {
+        counters
+            .iter()
+            .fold((0, 0), |(rx, tx), &(r, t)| (rx + r, tx + t))
+    })
+}
+
+// volume percent and whether it is on, from the first channel amixer lists
+fn parse_amixer(output: &str) -> Option<(u32, bool)> {
+    output.lines().find_map(|line| {
+        let start = line.find('[')? + 1;
+        let end = start + line[start..].find("%]")?;
+        let volume = line[start..end].parse().ok()?;
+        Some((volume, !line.contains("[off]")))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cpu_usage_comes_from_two_samples() {
+        let before = parse_cpu("cpu  100 0 100 700 100 0 0 0 50 50\ncpu0 1 2 3 4\n").unwrap();
+        let after = parse_cpu("cpu  200 0 200 1200 100 0 0 0 0 0\n").unwrap();
+
+        assert_eq!(before, (800, 1000));
+        assert_eq!(cpu_usage(before, after), 28);
+        assert_eq!(cpu_usage(after, after), 0);
+    }
+
+    #[test]
+    fn memory_uses_the_available_estimate() {
+        let meminfo =
+            "MemTotal:       16000000 kB\nMemFree:  1000 kB\nMemAvailable:   12000000 kB\n";
+        assert_eq!(parse_meminfo(meminfo), Some((16000000, 12000000)));
+        assert_eq!(parse_meminfo("MemTotal: 1 kB\n"), None);
+    }
+
+    #[test]
+    fn network_counters_skip_the_loopback() {
+        let dev = "Inter-|   Receive                                                |  Transmit
+ face |bytes    packets errs drop fifo frame compressed multicast|bytes    packets errs drop fifo colls carrier compressed
+    lo:  5000      10    0    0    0     0          0         0     5000      10    0    0    0     0       0          0
+  eth0: 1000       5    0    0    0     0          0         0      200       2    0    0    0     0       0          0
+ wlan0:   30       1    0    0    0     0          0         0        4       1    0    0    0     0       0          0
+";
+        assert_eq!(parse_net_dev(dev, "eth0"), Some((1000, 200)));
+        assert_eq!(parse_net_dev(dev, ""), Some((1030, 204)));
+        assert_eq!(parse_net_dev(dev, "usb0"), None);
+    }
+
+    #[test]
+    fn amixer_output_gives_volume_and_mute() {
+        let on = "Simple mixer control 'Master',0\n  Capabilities: pvolume pswitch\n  Front Left: Playback 55705 [85%] [on]\n  Front Right: Playback 55705 [85%] [on]\n";
+        let off = "  Mono: Playback 31 [40%] [-20.00dB] [off]\n";
+
+        assert_eq!(parse_amixer(on), Some((85, true)));
+        assert_eq!(parse_amixer(off), Some((40, false)));
+        assert_eq!(parse_amixer("amixer: Unable to find simple control"), None);
+    }
+
+    #[test]
+    fn formats_fill_every_placeholder() {
+        let text = fill(
+            "{used}/{total} {used}",
+            &[("used", human(1536 * 1024)), ("total", human(8 << 30))],
+        );
+        assert_eq!(text, "1.5M/8.0G 1.5M");
+        assert_eq!(human(512), "512B");
+        assert_eq!(human(20 * 1024), "20K");
+    }
+}
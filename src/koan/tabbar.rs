@@ -0,0 +1,139 @@
+use super::{
+    config::{Color, TAB_BAR_HEIGHT},
+    layouts::{get_layout, Rect},
+    modeline::ellipsize,
+    KoanWM, Window,
+};
+
+const TAB_PADDING: i32 = 5;
+
+impl KoanWM {
+    pub fn place_tab_bar(&mut self, mon_idx: usize, x: i32, y: i32, w: u32) {
+        let bar = match self.tab_bars.get(&mon_idx) {
+            Some(&bar) => bar,
+            None => {
+                let bar = self.create_tab_bar();
+                self.tab_bars.insert(mon_idx, bar);
+                bar
+            }
+        };
+
+        self.backend.move_resize(
+            bar,
+            Rect {
+                x,
+                y,
+                w,
+                h: TAB_BAR_HEIGHT,
+            },
+        );
+        self.backend.map_raised(bar);
+    }
+
+    fn create_tab_bar(&self) -> Window {
+        let rect = Rect {
+            x: 0,
+            y: 0,
+            w: 1,
+            h: TAB_BAR_HEIGHT,
+        };
+        self.backend.create_bar(rect, Color::Secondary.hex(), true)
+    }
+
+    pub fn hide_tab_bar(&self, mon_idx: usize) {
+        if let Some(&bar) = self.tab_bars.get(&mon_idx) {
+            self.backend.unmap(bar);
+        }
+    }
+
+    pub fn draw_tab_bars(&self) {
+        for (&mon_idx, &bar) in self.tab_bars.iter() {
+            if !self
+                .monitors
+                .get(mon_idx)
+                .is_some_and(|m| get_layout(m.layout).tab_bar())
+            {
+                continue;
+            }
+
+            let clients = self.tiled_clients(mon_idx);
+            if clients.is_empty() {
+                continue;
+            }
+
+            let width = match self.get_window_geometry(bar) {
+                Some(attrs) => attrs.width,
+                None => continue,
+            };
+            let baseline = self.backend.text_baseline(TAB_BAR_HEIGHT);
+
+            self.backend.clear(bar);
+
+            for (i, client) in clients.iter().enumerate() {
+                let (x, end) = tab_bounds(width, clients.len(), i);
+                let tab_w = end - x;
+                let (fg, bg) = if self.focused == Some(client.window) {
+                    (Color::Secondary, Color::Primary)
+                } else {
+                    (Color::Primary, Color::Secondary)
+                };
+
+                let tab = Rect {
+                    x,
+                    y: 0,
+                    w: tab_w as u32,
+                    h: TAB_BAR_HEIGHT,
+                };
+                self.backend.fill_rect(bar, tab, bg.hex());
+
+                let title = ellipsize(
+                    self.backend.as_ref(),
+                    &self.get_window_title(Some(client.window)),
+                    tab_w - TAB_PADDING * 2,
+                );
+                self.backend
+                    .draw_text(bar, x + TAB_PADDING, baseline, &title, fg.hex());
+            }
+
+            self.backend.flush();
+        }
+    }
+
+    // client whose tab is under `x`, if `bar` is one of the tab bars
+    pub fn tab_at(&self, bar: Window, x: i32) -> Option<Window> {
+        let (&mon_idx, _) = self.tab_bars.iter().find(|&(_, &b)| b == bar)?;
+        let clients = self.tiled_clients(mon_idx);
+        if clients.is_empty() {
+            return None;
+        }
+
+        let width = self.get_window_geometry(bar)?.width;
+        (0..clients.len())
+            .find(|&i| {
+                let (start, end) = tab_bounds(width, clients.len(), i);
+                x >= start && x < end
+            })
+            .map(|i| clients[i].window)
+    }
+}
+
+// x range of tab `i` out of `n`, the last one takes the pixels left over
+fn tab_bounds(width: i32, n: usize, i: usize) -> (i32, i32) {
+    let tab_w = width / n as i32;
+    let start = i as i32 * tab_w;
+    let end = if i + 1 == n { width } else { start + tab_w };
+    (start, end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tabs_cover_the_whole_bar() {
+        assert_eq!(tab_bounds(100, 3, 0), (0, 33));
+        assert_eq!(tab_bounds(100, 3, 1), (33, 66));
+        assert_eq!(tab_bounds(100, 3, 2), (66, 100));
+        assert_eq!(tab_bounds(100, 1, 0), (0, 100));
+    }
+}
@@ -0,0 +1,267 @@
+use super::{
+    config::{Color, MODELINE_HEIGHT, SYSTEM_TRAY},
+    layouts::Rect,
+    KoanWM, Window,
+};
+
+impl KoanWM {
+    // the tray window stays unmapped until the first icon docks
+    pub fn init_tray(&mut self) {
+        if !SYSTEM_TRAY {
+            return;
+        }
+
+        let rect = Rect {
+            x: 0,
+            y: 0,
+            w: 1,
+            h: MODELINE_HEIGHT,
+        };
+        let tray = self.backend.create_bar(rect, Color::Secondary.hex(), false);
+        if self.backend.acquire_tray(tray) {
+            self.tray = Some(tray);
+        } else {
+            eprintln!("Another system tray is running");
+            self.backend.destroy_window(tray);
+        }
+    }
+
+    pub fn handle_tray_dock(&mut self, icon: Window) {
+        let Some(tray) = self.tray else { return };
+        if self.tray_icons.contains(&icon) || self.clients.iter().any(|c| c.window == icon) {
+            return;
+        }
+
+        // reparenting a mapped icon unmaps and maps it again
+        if self.backend.dock_icon(tray, icon, Color::Secondary.hex()) {
+            *self.ignored_unmaps.entry(icon).or_default() += 1;
+        } else {
+            self.hidden_tray_icons.insert(icon);
+        }
+        self.tray_icons.push(icon);
+        self.place_tray();
+    }
+
+    // true when `window` was a tray icon. An icon that unmapped itself goes
+    // back to the root window, a destroyed one has nowhere to go
+    pub fn remove_tray_icon(&mut self, window: Window, undock: bool) -> bool {
+        let Some(pos) = self.tray_icons.iter().position(|&i| i == window) else {
+            return false;
+        };
+        self.tray_icons.remove(pos);
+        self.hidden_tray_icons.remove(&window);
+        if undock {
+            self.backend.undock_icon(window);
+        }
+        self.place_tray();
+        true
+    }
+
+    // icons that asked to be shown through their _XEMBED_INFO flags
+    fn shown_tray_icons(&self) -> Vec<Window> {
+        self.tray_icons
+            .iter()
+            .copied()
+            .filter(|&icon| !self.hidden_tray_icons.contains(&icon))
+            .collect()
+    }
+
+    // primary monitor, or the first one with a modeline when it has none
+    pub fn tray_monitor(&self) -> Option<usize> {
+        self.monitors
+            .iter()
+            .position(|m| m.primary)
+            .filter(|i| self.modelines.contains_key(i))
+            .or_else(|| self.modelines.keys().min().copied())
+    }
+
+    // room the icons take at the right of the modeline of `mon_idx`
+    pub fn tray_width(&self, mon_idx: usize) -> i32 {
+        if self.tray.is_none() || self.tray_monitor() != Some(mon_idx) {
+            return 0;
+        }
+        (self.shown_tray_icons().len() as u32 * MODELINE_HEIGHT) as i32
+    }
+
+    pub fn place_tray(&mut self) {
+        let Some(tray) = self.tray else { return };
+
+        for icon in self.tray_icons.clone() {
+            if self.backend.xembed_mapped(icon) {
+                self.hidden_tray_icons.remove(&icon);
+            } else if self.hidden_tray_icons.insert(icon) {
+                self.backend.unmap(icon);
+                *self.ignored_unmaps.entry(icon).or_default() += 1;
+            }
+        }
+        let icons = self.shown_tray_icons();
+
+        let modeline = self
+            .tray_monitor()
+            .and_then(|mon_idx| self.modelines.get(&mon_idx))
+            .and_then(|&modeline| self.get_window_geometry(modeline));
+        match modeline {
+            Some(bar) if !icons.is_empty() => {
+                let width = icons.len() as u32 * MODELINE_HEIGHT;
+                let rect = Rect {
+                    x: bar.x + bar.width - width as i32,
+                    y: bar.y,
+                    w: width,
+                    h: MODELINE_HEIGHT,
+                };
+                self.backend.move_resize(tray, rect);
+                self.backend.map_raised(tray);
+
+                for (i, &icon) in icons.iter().enumerate() {
+                    let rect = Rect {
+                        x: (i as u32 * MODELINE_HEIGHT) as i32,
+                        y: 0,
+                        w: MODELINE_HEIGHT,
+                        h: MODELINE_HEIGHT,
+                    };
+                    self.backend.move_resize(icon, rect);
+                    self.backend.map(icon);
+                }
+            }
+            _ => self.backend.unmap(tray),
+        }
+        self.update_modelines();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::koan::{
+        backend::{
+            fake::{Fake, SCREENS},
+            Backend, Event,
+        },
+        config::MODELINE_HEIGHT,
+        layouts::Rect,
+        modeline::{Alignment, Module, ModuleType},
+    };
+
+    #[test]
+    fn icons_dock_at_the_right_of_the_primary_modeline() {
+        static MODULES: &[Module] = &[Module {
+            kind: ModuleType::Clock,
+            alignment: Alignment::Right,
+            fg: None,
+            bg: None,
+            clicks: &[],
+        }];
+        let fake = Fake::new(&SCREENS);
+        fake.state.borrow_mut().monitors[1].primary = true;
+        fake.state.borrow_mut().monitors[0].primary = false;
+        let mut wm = fake.koan();
+        wm.modules = MODULES;
+        wm.create_modelines();
+        wm.init_tray();
+        let tray = wm.tray.unwrap();
+
+        let a = fake.create_window(64, 64);
+        let b = fake.create_window(22, 22);
+        fake.push(Event::TrayDock(a));
+        fake.push(Event::TrayDock(b));
+        wm.run().unwrap();
+
+        let size = MODELINE_HEIGHT;
+        assert_eq!(
+            fake.geometry(tray),
+            Rect {
+                x: 1920 + 1280 - 2 * size as i32,
+                y: 0,
+                w: 2 * size,
+                h: size,
+            }
+        );
+        assert_eq!(
+            fake.geometry(b),
+            Rect {
+                x: size as i32,
+                y: 0,
+                w: size,
+                h: size,
+            }
+        );
+        assert_eq!(fake.with_window(a, |w| w.embedder), Some(tray));
+        assert!(fake.with_window(tray, |w| w.mapped));
+
+        // the clock moves left of the icons, the other modeline keeps its room
+        let clock_end = |mon_idx| {
+            let modeline = wm.modelines[&mon_idx];
+            fake.with_window(modeline, |w| {
+                let (x, text) = w.texts.last().unwrap();
+                x + fake.text_width(text)
+            })
+        };
+        assert!(clock_end(1) <= 1280 - 2 * size as i32);
+        assert!(clock_end(0) > 1920 - 2 * size as i32);
+    }
+
+    #[test]
+    fn icons_leave_the_tray_when_they_go_away() {
+        let fake = Fake::new(&SCREENS[..1]);
+        let mut wm = fake.koan();
+        wm.create_modelines();
+        wm.init_tray();
+        let a = fake.create_window(22, 22);
+        let b = fake.create_window(22, 22);
+        fake.with_window(b, |w| w.mapped = true);
+
+        // docking the mapped icon unmaps it, that does not remove it
+        fake.push(Event::TrayDock(a));
+        fake.push(Event::TrayDock(b));
+        fake.push(Event::UnmapNotify(b));
+        fake.push(Event::DestroyNotify(a));
+        wm.run().unwrap();
+        assert_eq!(wm.tray_icons, vec![b]);
+
+        fake.push(Event::UnmapNotify(b));
+        wm.run().unwrap();
+        assert!(wm.tray_icons.is_empty());
+        assert_eq!(fake.with_window(b, |w| w.embedder), None);
+        assert!(!fake.with_window(wm.tray.unwrap(), |w| w.mapped));
+    }
+
+    #[test]
+    fn icons_follow_their_xembed_mapped_flag() {
+        let fake = Fake::new(&SCREENS[..1]);
+        let mut wm = fake.koan();
+        wm.create_modelines();
+        wm.init_tray();
+        let icon = fake.create_window(22, 22);
+        fake.with_window(icon, |w| w.mapped = true);
+        fake.with_window(icon, |w| w.properties.insert(wm.xembed_info, vec![0, 0]));
+
+        fake.push(Event::TrayDock(icon));
+        wm.run().unwrap();
+        assert!(!fake.with_window(icon, |w| w.mapped));
+        assert_eq!(wm.tray_width(0), 0);
+
+        // koan unmapped it, the icon is still docked
+        fake.push(Event::UnmapNotify(icon));
+        fake.with_window(icon, |w| w.properties.insert(wm.xembed_info, vec![0, 1]));
+        fake.push(Event::PropertyNotify {
+            window: icon,
+            atom: wm.xembed_info,
+        });
+        wm.run().unwrap();
+        assert_eq!(wm.tray_icons, vec![icon]);
+        assert!(fake.with_window(icon, |w| w.mapped));
+        assert_eq!(wm.tray_width(0), MODELINE_HEIGHT as i32);
+    }
+
+    #[test]
+    fn a_second_tray_is_refused() {
+        let fake = Fake::new(&SCREENS[..1]);
+        fake.state.borrow_mut().tray = Some(1);
+        let mut wm = fake.koan();
+        wm.init_tray();
+
+        assert_eq!(wm.tray, None);
+        fake.push(Event::TrayDock(2));
+        wm.run().unwrap();
+        assert!(wm.tray_icons.is_empty());
+    }
+}
@@ -1,8 +1,6 @@
-use x11::xlib;
+use crate::koan::{KoanWM, KoanWMError, BORDER_WIDTH};
 
-use crate::koan::{get_pixel_from_color, KoanWM, KoanWMError, BORDER_WIDTH};
-
-use super::{config::Color, layouts::LAYOUTS};
+use super::{backend::WindowAttributes, config::Color, layouts::get_layout};
 
 #[derive(Copy, Clone)]
 pub enum Direction {
@@ -12,198 +10,165 @@ pub enum Direction {
     Down,
 }
 
-pub type Window = u64;
+impl Direction {
+    pub fn opposite(self) -> Self {
+        match self {
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+        }
+    }
+}
+
+pub type Window = u32;
 
 pub struct Client {
     pub window: Window,
     pub is_float: bool,
+    pub is_urgent: bool,
 }
 
 impl KoanWM {
     fn should_manage_window(&self, window: Window) -> bool {
-        unsafe {
-            if self.clients.iter().any(|c| c.window == window) {
-                eprintln!("Window {} already managed", window);
-                return false;
-            }
-
-            let mut wa: xlib::XWindowAttributes = std::mem::zeroed();
-            if xlib::XGetWindowAttributes(self.display, window, &mut wa) == 0 {
-                eprintln!("Failed to get window attributes for {}", window);
-                return false;
-            }
-
-            if wa.override_redirect != 0 {
-                eprintln!("Window {} is override-redirect, ignoring", window);
-                return false;
-            }
+        if self.clients.iter().any(|c| c.window == window) {
+            eprintln!("Window {} already managed", window);
+            return false;
+        }
 
-            if self.is_auxiliary_window_type(window) {
-                eprintln!("Window {} is auxiliary type, ignoring", window);
-                return false;
-            }
+        let Some(wa) = self.backend.window_attributes(window) else {
+            eprintln!("Failed to get window attributes for {}", window);
+            return false;
+        };
 
-            if wa.width < 1 || wa.height < 1 {
-                eprintln!(
-                    "Window {} has dimensions {}x{}, possibly auxiliary",
-                    window, wa.width, wa.height
-                );
-                return false;
-            }
+        if wa.override_redirect {
+            eprintln!("Window {} is override-redirect, ignoring", window);
+            return false;
+        }
 
-            eprintln!("Window {} passed all checks, managing it", window);
-            true
+        if self.is_auxiliary_window_type(window) {
+            eprintln!("Window {} is auxiliary type, ignoring", window);
+            return false;
         }
-    }
 
-    fn is_auxiliary_window_type(&self, window: Window) -> bool {
-        unsafe {
-            let net_wm_window_type = xlib::XInternAtom(
-                self.display,
-                b"_NET_WM_WINDOW_TYPE\0".as_ptr() as *const i8,
-                xlib::False,
+        if wa.width < 1 || wa.height < 1 {
+            eprintln!(
+                "Window {} has dimensions {}x{}, possibly auxiliary",
+                window, wa.width, wa.height
             );
+            return false;
+        }
 
-            let mut actual_type: xlib::Atom = 0;
-            let mut actual_format: i32 = 0;
-            let mut nitems: u64 = 0;
-            let mut bytes_after: u64 = 0;
-            let mut prop: *mut u8 = std::ptr::null_mut();
-
-            let result = xlib::XGetWindowProperty(
-                self.display,
-                window,
-                net_wm_window_type,
-                0,
-                1,
-                xlib::False,
-                xlib::XA_ATOM,
-                &mut actual_type,
-                &mut actual_format,
-                &mut nitems,
-                &mut bytes_after,
-                &mut prop,
-            );
+        eprintln!("Window {} passed all checks, managing it", window);
+        true
+    }
 
-            if result != 0 || prop.is_null() || nitems == 0 {
-                return false;
-            }
+    fn is_auxiliary_window_type(&self, window: Window) -> bool {
+        let net_wm_window_type = self.backend.intern_atom("_NET_WM_WINDOW_TYPE");
+        let Some(&window_type) = self.backend.get_atoms(window, net_wm_window_type).first() else {
+            return false;
+        };
 
-            let window_type = *(prop as *const xlib::Atom);
-            xlib::XFree(prop as *mut std::ffi::c_void);
-
-            let ignore_types: &[&[u8]] = &[
-                b"_NET_WM_WINDOW_TYPE_SPLASH\0",
-                b"_NET_WM_WINDOW_TYPE_TOOLBAR\0",
-                b"_NET_WM_WINDOW_TYPE_MENU\0",
-                b"_NET_WM_WINDOW_TYPE_DROPDOWN_MENU\0",
-                b"_NET_WM_WINDOW_TYPE_POPUP_MENU\0",
-                b"_NET_WM_WINDOW_TYPE_TOOLTIP\0",
-                b"_NET_WM_WINDOW_TYPE_NOTIFICATION\0",
-                b"_NET_WM_WINDOW_TYPE_COMBO\0",
-                b"_NET_WM_WINDOW_TYPE_DND\0",
-            ];
-
-            for ignore_type in ignore_types {
-                let atom =
-                    xlib::XInternAtom(self.display, ignore_type.as_ptr() as *const i8, xlib::False);
-                if window_type == atom {
-                    eprintln!("Window {} has auxiliary type", window);
-                    return true;
-                }
+        let ignore_types = [
+            "_NET_WM_WINDOW_TYPE_SPLASH",
+            "_NET_WM_WINDOW_TYPE_TOOLBAR",
+            "_NET_WM_WINDOW_TYPE_MENU",
+            "_NET_WM_WINDOW_TYPE_DROPDOWN_MENU",
+            "_NET_WM_WINDOW_TYPE_POPUP_MENU",
+            "_NET_WM_WINDOW_TYPE_TOOLTIP",
+            "_NET_WM_WINDOW_TYPE_NOTIFICATION",
+            "_NET_WM_WINDOW_TYPE_COMBO",
+            "_NET_WM_WINDOW_TYPE_DND",
+        ];
+
+        for ignore_type in ignore_types {
+            if window_type == self.backend.intern_atom(ignore_type) {
+                eprintln!("Window {} has auxiliary type", window);
+                return true;
             }
-
-            false
         }
+
+        false
     }
 
     pub fn manage_window(&mut self, window: Window) -> Result<(), KoanWMError> {
         eprintln!("=== Attempting to manage window {} ===", window);
 
-        unsafe {
-            if !self.should_manage_window(window) {
-                eprintln!("Window {} rejected by rules", window);
-                return Ok(());
-            }
+        if !self.should_manage_window(window) {
+            eprintln!("Window {} rejected by rules", window);
+            return Ok(());
+        }
 
-            eprintln!("Managing window {}", window);
+        eprintln!("Managing window {}", window);
 
-            let mut client: Client = Client {
-                window,
-                is_float: false,
-            };
+        let mut client: Client = Client {
+            window,
+            is_float: false,
+            is_urgent: false,
+        };
 
-            let mut transient_for: xlib::Window = 0;
-            if xlib::XGetTransientForHint(self.display, window, &mut transient_for) != 0 {
-                eprintln!("Window {} is transient", window);
-                xlib::XMapRaised(self.display, window);
-                xlib::XChangeProperty(
-                    self.display,
-                    window,
-                    self.wm_state,
-                    xlib::XA_ATOM,
-                    32,
-                    xlib::PropModeAppend,
-                    &self.wm_state_above as *const xlib::Atom as *const u8,
-                    1,
-                );
-                client.is_float = true;
-                self.center_window(client.window);
-                self.window_monitors
-                    .insert(client.window, self.current_monitor);
-                self.clients.push(client);
-                return Ok(());
-            }
+        // dialogs need their property changes too, like title updates
+        self.backend.select_client_input(window);
+
+        if self.backend.transient_for(window).is_some() {
+            eprintln!("Window {} is transient", window);
+            self.backend.map_raised(window);
+            self.backend
+                .append_atom(window, self.wm_state, self.wm_state_above);
+            client.is_float = true;
+            self.center_window(client.window);
+            self.window_monitors
+                .insert(client.window, self.current_monitor);
+            self.set_desktop(window, self.monitors[self.current_monitor].desktop);
+            self.clients.push(client);
+            return Ok(());
+        }
 
-            match self.layout {
-                LAYOUTS::MASTER => self.clients.push(client),
-                LAYOUTS::MONOCLE => self.clients.insert(0, client),
-            }
-            self.window_monitors.insert(window, self.current_monitor);
+        let mon_idx = self.app_monitor(window).unwrap_or(self.current_monitor);
+        if get_layout(self.monitors[mon_idx].layout).insert_first() {
+            self.clients.insert(0, client);
+        } else {
+            self.clients.push(client);
+        }
+        self.window_monitors.insert(window, mon_idx);
+        self.set_desktop(window, self.monitors[mon_idx].desktop);
 
-            xlib::XSelectInput(
-                self.display,
-                window,
-                xlib::EnterWindowMask | xlib::FocusChangeMask,
-            );
-            xlib::XSetWindowBorderWidth(self.display, window, BORDER_WIDTH);
+        self.backend.set_border_width(window, BORDER_WIDTH);
 
-            self.layout()?;
-            xlib::XMapRaised(self.display, window);
-            xlib::XFlush(self.display);
-            self.focus_window(window);
+        self.layout()?;
+        self.backend.map_raised(window);
+        self.backend.flush();
+        self.focus_window(window);
 
-            eprintln!("Successfully managed window {}", window);
-        }
+        eprintln!("Successfully managed window {}", window);
 
         Ok(())
     }
 
     pub fn focus_window(&mut self, window: Window) {
-        if let Some(old_win) = self.focused {
-            if old_win != window {
-                self.set_border_color(old_win, Color::Secondary.hex());
-            }
+        if let Some(old_win) = self.focused
+            && old_win != window
+        {
+            self.backend
+                .set_border_color(old_win, Color::Secondary.hex());
         }
 
         if let Some(&mon_idx) = self.window_monitors.get(&window) {
             self.current_monitor = mon_idx;
+            self.monitors[mon_idx].focused = Some(window);
         }
 
         self.focused = Some(window);
-
-        unsafe {
-            xlib::XSetInputFocus(
-                self.display,
-                window,
-                xlib::RevertToPointerRoot,
-                xlib::CurrentTime,
-            );
-            xlib::XRaiseWindow(self.display, window);
+        if let Some(client) = self.clients.iter_mut().find(|c| c.window == window) {
+            client.is_urgent = false;
         }
 
-        self.set_border_color(window, Color::Primary.hex());
+        self.backend.set_input_focus(window);
+        self.backend.raise(window);
+        self.backend.set_border_color(window, Color::Primary.hex());
+        self.update_current_desktop();
         self.update_modelines();
+        self.draw_tab_bars();
     }
 
     pub fn swap_window(&mut self, next: bool) {
@@ -216,7 +181,7 @@ impl KoanWM {
             .clients
             .iter()
             .enumerate()
-            .filter(|&(_, c)| self.window_monitors.get(&c.window) == Some(&self.current_monitor))
+            .filter(|&(_, c)| self.is_shown(c.window, self.current_monitor))
             .map(|(i, _)| i)
             .collect();
 
@@ -268,7 +233,8 @@ impl KoanWM {
             Direction::Down => (0, step),
         };
 
-        unsafe { xlib::XMoveWindow(self.display, focused, geometry.x + dx, geometry.y + dy) };
+        self.backend
+            .move_window(focused, geometry.x + dx, geometry.y + dy);
     }
 
     pub fn resize_window(&mut self, dir: Direction) {
@@ -291,14 +257,11 @@ impl KoanWM {
             Direction::Down => (0, step),
         };
 
-        unsafe {
-            xlib::XResizeWindow(
-                self.display,
-                focused,
-                (geometry.width + dw) as u32,
-                (geometry.height + dh) as u32,
-            );
-        }
+        self.backend.resize_window(
+            focused,
+            (geometry.width + dw) as u32,
+            (geometry.height + dh) as u32,
+        );
     }
 
     pub fn toggle_float(&mut self) {
@@ -307,30 +270,31 @@ impl KoanWM {
             None => return,
         };
 
-        if let Some(client) = self.clients.iter_mut().find(|c| c.window == focused) {
-            client.is_float = !client.is_float;
+        let Some(client) = self.clients.iter_mut().find(|c| c.window == focused) else {
+            return;
+        };
+        client.is_float = !client.is_float;
+        if client.is_float {
+            self.center_window(focused);
         }
 
         let _ = self.layout();
     }
 
     pub fn center_window(&self, window: Window) {
-        let mon = self.monitors[self.current_monitor];
-        let mut attr: xlib::XWindowAttributes = unsafe { std::mem::zeroed() };
-
-        unsafe {
-            xlib::XGetWindowAttributes(self.display, window, &mut attr);
-            let x = mon.x + (mon.width - attr.width) / 2;
-            let y = mon.y + (mon.height - attr.height) / 2;
-            xlib::XMoveWindow(self.display, window, x, y);
-        }
+        let mon = &self.monitors[self.current_monitor];
+        let attr = self.get_window_geometry(window).unwrap_or_default();
+
+        let x = mon.x + (mon.width - attr.width) / 2;
+        let y = mon.y + (mon.height - attr.height) / 2;
+        self.backend.move_window(window, x, y);
     }
 
     pub fn cycle_focus(&mut self, next: bool) {
         let current_client: Vec<&Client> = self
             .clients
             .iter()
-            .filter(|c| self.window_monitors.get(&c.window) == Some(&self.current_monitor))
+            .filter(|c| self.is_shown(c.window, self.current_monitor))
             .collect();
 
         if current_client.is_empty() {
@@ -364,40 +328,16 @@ impl KoanWM {
         }
     }
 
-    pub fn set_border_color(&self, window: Window, color: &str) {
-        let pixel = get_pixel_from_color(self.display, color);
-        unsafe {
-            xlib::XSetWindowBorder(self.display, window, pixel);
-            xlib::XFlush(self.display);
-        }
-    }
-
     pub fn send_delete(&mut self, window: Window) -> Result<(), KoanWMError> {
-        unsafe {
-            let mut client_ev = xlib::XClientMessageEvent {
-                type_: xlib::ClientMessage,
-                serial: 0,
-                send_event: 1,
-                display: self.display,
-                window,
-                message_type: self.wm_protocols,
-                format: 32,
-                data: xlib::ClientMessageData::new(),
-            };
-            client_ev.data.set_long(0, self.wm_delete as i64);
-            client_ev.data.set_long(1, xlib::CurrentTime as i64);
-            let mut event: xlib::XEvent = client_ev.into();
-            xlib::XSendEvent(self.display, window, 0, xlib::NoEventMask, &mut event);
-            xlib::XFlush(self.display);
-            Ok(())
-        }
+        self.backend.send_protocol(window, self.wm_delete);
+        Ok(())
     }
 
-    pub fn _get_adjacent_window(&self, next: bool) -> Option<xlib::Window> {
+    pub fn _get_adjacent_window(&self, next: bool) -> Option<Window> {
         let monitor_windows: Vec<&Client> = self
             .clients
             .iter()
-            .filter(|&c| self.window_monitors.get(&c.window) == Some(&self.current_monitor))
+            .filter(|&c| self.is_shown(c.window, self.current_monitor))
             .collect();
 
         if monitor_windows.is_empty() {
@@ -428,15 +368,8 @@ impl KoanWM {
         }
     }
 
-    pub fn get_window_geometry(&self, window: xlib::Window) -> Option<xlib::XWindowAttributes> {
-        unsafe {
-            let mut attrs: xlib::XWindowAttributes = std::mem::zeroed();
-            if xlib::XGetWindowAttributes(self.display, window, &mut attrs) != 0 {
-                Some(attrs)
-            } else {
-                None
-            }
-        }
+    pub fn get_window_geometry(&self, window: Window) -> Option<WindowAttributes> {
+        self.backend.window_attributes(window)
     }
 
     pub fn focused_client(&self) -> Option<&Client> {
@@ -449,35 +382,138 @@ impl KoanWM {
         // if xlib::XGetWindowProperty(self.display, 11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1)
     }
 
-    pub fn get_window_title(&self, window: Window) -> String {
-        unsafe {
-            let mut name_ptr: *mut i8 = std::ptr::null_mut();
-            if xlib::XFetchName(self.display, window, &mut name_ptr) != 0 && !name_ptr.is_null() {
-                let c_str = std::ffi::CStr::from_ptr(name_ptr);
-                let title = c_str.to_string_lossy().into_owned();
-                xlib::XFree(name_ptr as *mut _);
-                title
-            } else {
-                "No Name".to_string()
-            }
+    // title of `window` for the bars, None being the focus on an empty monitor
+    pub fn get_window_title(&self, window: Option<Window>) -> String {
+        match window {
+            Some(w) => self
+                .backend
+                .window_title(w)
+                .unwrap_or_else(|| "No Name".to_string()),
+            None => String::from("Empty"),
         }
     }
+}
 
-    pub fn get_focus_title(&self) -> String {
-        let focused = match self.focused {
-            Some(w) => w,
-            None => return String::from("Empty"),
-        };
-        unsafe {
-            let mut name_ptr: *mut i8 = std::ptr::null_mut();
-            if xlib::XFetchName(self.display, focused, &mut name_ptr) != 0 && !name_ptr.is_null() {
-                let c_str = std::ffi::CStr::from_ptr(name_ptr);
-                let title = c_str.to_string_lossy().into_owned();
-                xlib::XFree(name_ptr as *mut _);
-                title
-            } else {
-                "No Name".to_string()
-            }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::koan::{
+        backend::fake::{Fake, SCREEN},
+        layouts::Rect,
+    };
+
+    #[test]
+    fn managed_windows_are_mapped_focused_and_tiled() {
+        let fake = Fake::new(&[SCREEN]);
+        let mut wm = fake.koan();
+        let win = fake.create_window(300, 200);
+
+        wm.manage_window(win).unwrap();
+
+        assert_eq!(wm.clients.len(), 1);
+        assert_eq!(wm.focused, Some(win));
+        assert_eq!(fake.focus(), Some(win));
+        fake.with_window(win, |w| {
+            assert!(w.mapped);
+            assert_eq!(w.border_width, BORDER_WIDTH);
+            assert_eq!(w.border_color, Color::Primary.hex());
+        });
+        // monocle fills the monitor
+        assert!(fake.geometry(win).w > 1800);
+    }
+
+    #[test]
+    fn windows_are_only_managed_once() {
+        let fake = Fake::new(&[SCREEN]);
+        let mut wm = fake.koan();
+        let win = fake.create_window(300, 200);
+
+        wm.manage_window(win).unwrap();
+        wm.manage_window(win).unwrap();
+
+        assert_eq!(wm.clients.len(), 1);
+    }
+
+    #[test]
+    fn override_redirect_and_auxiliary_windows_are_ignored() {
+        let fake = Fake::new(&[SCREEN]);
+        let mut wm = fake.koan();
+
+        let popup = fake.create_window(300, 200);
+        fake.with_window(popup, |w| w.attrs.override_redirect = true);
+
+        let tooltip = fake.create_window(300, 200);
+        let window_type = wm.backend.intern_atom("_NET_WM_WINDOW_TYPE");
+        let tooltip_type = wm.backend.intern_atom("_NET_WM_WINDOW_TYPE_TOOLTIP");
+        wm.backend.append_atom(tooltip, window_type, tooltip_type);
+
+        let empty = fake.create_window(0, 0);
+
+        for win in [popup, tooltip, empty] {
+            wm.manage_window(win).unwrap();
         }
+
+        assert!(wm.clients.is_empty());
+        assert_eq!(wm.focused, None);
+    }
+
+    #[test]
+    fn transient_windows_float_above() {
+        let fake = Fake::new(&[SCREEN]);
+        let mut wm = fake.koan();
+        let parent = fake.create_window(300, 200);
+        let dialog = fake.create_window(400, 300);
+        fake.with_window(dialog, |w| w.transient_for = Some(parent));
+
+        wm.manage_window(parent).unwrap();
+        wm.manage_window(dialog).unwrap();
+
+        assert!(wm.clients.iter().any(|c| c.window == dialog && c.is_float));
+        assert_eq!(
+            wm.backend.get_atoms(dialog, wm.wm_state),
+            vec![wm.wm_state_above]
+        );
+        assert_eq!(
+            fake.geometry(dialog),
+            Rect {
+                x: 760,
+                y: 390,
+                w: 400,
+                h: 300
+            }
+        );
+    }
+
+    #[test]
+    fn focus_moves_the_highlighted_border() {
+        let fake = Fake::new(&[SCREEN]);
+        let mut wm = fake.koan();
+        let a = fake.create_window(300, 200);
+        let b = fake.create_window(300, 200);
+        wm.manage_window(a).unwrap();
+        wm.manage_window(b).unwrap();
+
+        wm.focus_window(a);
+
+        assert_eq!(fake.focus(), Some(a));
+        fake.with_window(a, |w| assert_eq!(w.border_color, Color::Primary.hex()));
+        fake.with_window(b, |w| assert_eq!(w.border_color, Color::Secondary.hex()));
+    }
+
+    #[test]
+    fn closing_asks_the_client_to_delete_itself() {
+        let fake = Fake::new(&[SCREEN]);
+        let mut wm = fake.koan();
+        let win = fake.create_window(300, 200);
+        wm.manage_window(win).unwrap();
+
+        wm.send_delete(win).unwrap();
+
+        assert_eq!(
+            fake.state.borrow().protocols_sent,
+            vec![(win, wm.wm_delete)]
+        );
+        // the window stays managed until the client unmaps it
+        assert_eq!(wm.clients.len(), 1);
     }
 }
@@ -0,0 +1,278 @@
+use std::{
+    io::{self, BufRead, BufReader},
+    os::{fd::AsRawFd, unix::process::CommandExt},
+    process::{Child, Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use x11rb::{
+    connection::Connection,
+    protocol::{
+        xproto::{
+            Atom, AtomEnum, ConnectionExt as _, CreateWindowAux, EventMask, Keysym, PropMode,
+            Window, WindowClass, KEY_PRESS_EVENT, KEY_RELEASE_EVENT,
+        },
+        xtest::ConnectionExt as _,
+        Event,
+    },
+    rust_connection::RustConnection,
+    wrapper::ConnectionExt as _,
+    COPY_DEPTH_FROM_PARENT, COPY_FROM_PARENT, CURRENT_TIME, NONE,
+};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+const POLL: Duration = Duration::from_millis(20);
+
+#[derive(Debug, Clone, Copy)]
+pub struct Geometry {
+    pub x: i16,
+    pub y: i16,
+    pub width: u16,
+    pub height: u16,
+    pub border: u16,
+}
+
+// killed on drop so a failing assertion does not leave servers behind
+struct Process(Child);
+
+impl Drop for Process {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+// an Xvfb server with koan managing it, plus a client connection for the test
+pub struct Session {
+    pub conn: RustConnection,
+    pub root: Window,
+    _koan: Process,
+    _xvfb: Process,
+}
+
+impl Session {
+    // None when Xvfb is not installed, the test should return early
+    pub fn start(screens: &[(u16, u16)]) -> Option<Self> {
+        Self::start_with_env(screens, &[])
+    }
+
+    pub fn start_with_env(screens: &[(u16, u16)], env: &[(&str, &str)]) -> Option<Self> {
+        // the server picks a free display itself and writes its number to
+        // the pipe, tests running in parallel never race for the same one
+        let (reader, writer) = io::pipe().expect("failed to create a pipe");
+        let fd = writer.as_raw_fd();
+        let mut xvfb = Command::new("Xvfb");
+        xvfb.args([
+            "-displayfd",
+            &fd.to_string(),
+            "-nolisten",
+            "tcp",
+            "-noreset",
+        ]);
+        for (i, (w, h)) in screens.iter().enumerate() {
+            xvfb.args(["-screen", &i.to_string(), &format!("{w}x{h}x24")]);
+        }
+        if screens.len() > 1 {
+            xvfb.arg("+xinerama");
+        }
+        // std pipes are close-on-exec, Xvfb has to keep the write end
+        unsafe {
+            xvfb.pre_exec(move || match libc::fcntl(fd, libc::F_SETFD, 0) {
+                -1 => Err(io::Error::last_os_error()),
+                _ => Ok(()),
+            });
+        }
+        let xvfb = match xvfb.stdout(Stdio::null()).stderr(Stdio::null()).spawn() {
+            Ok(child) => Process(child),
+            Err(_) => {
+                eprintln!("Xvfb is not available, skipping");
+                return None;
+            }
+        };
+        drop(writer);
+
+        let mut number = String::new();
+        BufReader::new(reader)
+            .read_line(&mut number)
+            .expect("failed to read the Xvfb display");
+        assert!(!number.trim().is_empty(), "Xvfb did not start");
+        let display = format!(":{}", number.trim());
+
+        let (conn, screen_num) =
+            wait_for(|| x11rb::connect(Some(&display)).ok()).expect("Xvfb did not start");
+        let root = conn.setup().roots[screen_num].root;
+
+        let koan = Command::new(env!("CARGO_BIN_EXE_koan"))
+            .env("DISPLAY", &display)
+            .env("KOAN_NO_AUTOSTART", "1")
+            .envs(env.iter().copied())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map(Process)
+            .expect("failed to launch koan");
+
+        let session = Self {
+            conn,
+            root,
+            _koan: koan,
+            _xvfb: xvfb,
+        };
+        assert!(
+            session.wait_until(|s| s.is_managed()),
+            "koan did not take over the root window"
+        );
+        Some(session)
+    }
+
+    fn is_managed(&self) -> bool {
+        self.conn
+            .get_window_attributes(self.root)
+            .unwrap()
+            .reply()
+            .is_ok_and(|a| a.all_event_masks.contains(EventMask::SUBSTRUCTURE_REDIRECT))
+    }
+
+    pub fn wait_until(&self, cond: impl Fn(&Self) -> bool) -> bool {
+        wait_for(|| cond(self).then_some(())).is_some()
+    }
+
+    pub fn create_window(&self, width: u16, height: u16) -> Window {
+        let window = self.conn.generate_id().unwrap();
+        self.conn
+            .create_window(
+                COPY_DEPTH_FROM_PARENT,
+                window,
+                self.root,
+                0,
+                0,
+                width,
+                height,
+                0,
+                WindowClass::INPUT_OUTPUT,
+                COPY_FROM_PARENT,
+                &CreateWindowAux::new().event_mask(EventMask::STRUCTURE_NOTIFY),
+            )
+            .unwrap();
+        window
+    }
+
+    pub fn map(&self, window: Window) {
+        self.conn.map_window(window).unwrap();
+        self.conn.flush().unwrap();
+    }
+
+    pub fn atom(&self, name: &str) -> Atom {
+        self.conn
+            .intern_atom(false, name.as_bytes())
+            .unwrap()
+            .reply()
+            .unwrap()
+            .atom
+    }
+
+    pub fn set_property(&self, window: Window, property: Atom, kind: AtomEnum, value: &[u32]) {
+        self.conn
+            .change_property32(PropMode::REPLACE, window, property, kind, value)
+            .unwrap();
+        self.conn.flush().unwrap();
+    }
+
+    pub fn atoms(&self, window: Window, property: Atom) -> Vec<Atom> {
+        self.conn
+            .get_property(false, window, property, AtomEnum::ATOM, 0, 32)
+            .unwrap()
+            .reply()
+            .ok()
+            .and_then(|r| r.value32().map(|v| v.collect()))
+            .unwrap_or_default()
+    }
+
+    pub fn geometry(&self, window: Window) -> Geometry {
+        let g = self.conn.get_geometry(window).unwrap().reply().unwrap();
+        Geometry {
+            x: g.x,
+            y: g.y,
+            width: g.width,
+            height: g.height,
+            border: g.border_width,
+        }
+    }
+
+    pub fn focus(&self) -> Window {
+        self.conn.get_input_focus().unwrap().reply().unwrap().focus
+    }
+
+    // children of the root window, bottom to top
+    pub fn stacking(&self) -> Vec<Window> {
+        self.conn
+            .query_tree(self.root)
+            .unwrap()
+            .reply()
+            .unwrap()
+            .children
+    }
+
+    // presses `key` while holding `modifiers`, through XTEST so the passive
+    // grabs of koan see it like a real key press
+    pub fn press(&self, modifiers: &[Keysym], key: Keysym) {
+        let codes: Vec<u8> = modifiers.iter().map(|&m| self.keycode(m)).collect();
+        let key = self.keycode(key);
+
+        for &code in codes.iter().chain([&key]) {
+            self.fake_key(KEY_PRESS_EVENT, code);
+        }
+        for &code in [&key].into_iter().chain(codes.iter().rev()) {
+            self.fake_key(KEY_RELEASE_EVENT, code);
+        }
+        self.conn.sync().unwrap();
+    }
+
+    fn fake_key(&self, kind: u8, keycode: u8) {
+        self.conn
+            .xtest_fake_input(kind, keycode, CURRENT_TIME, NONE, 0, 0, 0)
+            .unwrap();
+    }
+
+    fn keycode(&self, keysym: Keysym) -> u8 {
+        let setup = self.conn.setup();
+        let (min, max) = (setup.min_keycode, setup.max_keycode);
+        let mapping = self
+            .conn
+            .get_keyboard_mapping(min, max - min + 1)
+            .unwrap()
+            .reply()
+            .unwrap();
+        let per = mapping.keysyms_per_keycode as usize;
+        let idx = mapping
+            .keysyms
+            .iter()
+            .position(|&k| k == keysym)
+            .unwrap_or_else(|| panic!("keysym {keysym:#x} is not in the keymap"));
+        min + (idx / per) as u8
+    }
+
+    // next event on the test client's own connection
+    pub fn wait_for_event(&self, matches: impl Fn(&Event) -> bool) -> Option<Event> {
+        wait_for(|| {
+            while let Some(event) = self.conn.poll_for_event().unwrap() {
+                if matches(&event) {
+                    return Some(event);
+                }
+            }
+            None
+        })
+    }
+}
+
+fn wait_for<T>(mut f: impl FnMut() -> Option<T>) -> Option<T> {
+    let start = Instant::now();
+    while start.elapsed() < TIMEOUT {
+        if let Some(value) = f() {
+            return Some(value);
+        }
+        thread::sleep(POLL);
+    }
+    None
+}
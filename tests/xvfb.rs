@@ -0,0 +1,117 @@
+// end-to-end tests against a real X server, skipped when Xvfb is not installed
+
+mod common;
+
+use common::Session;
+use x11::keysym::{XK_Alt_L, XK_Shift_L, XK_c, XK_period};
+use x11rb::protocol::{xproto::AtomEnum, Event};
+
+const SCREEN: (u16, u16) = (800, 600);
+
+#[test]
+fn mapped_windows_are_tiled_and_focused() {
+    let Some(s) = Session::start(&[SCREEN]) else {
+        return;
+    };
+
+    let win = s.create_window(200, 100);
+    s.map(win);
+
+    assert!(s.wait_until(|s| {
+        let g = s.geometry(win);
+        g.width > 700 && g.height > 500
+    }));
+    assert!(s.wait_until(|s| s.focus() == win));
+    assert!(s.geometry(win).border > 0);
+}
+
+#[test]
+fn transient_windows_are_centered_above_their_parent() {
+    let Some(s) = Session::start(&[SCREEN]) else {
+        return;
+    };
+
+    let parent = s.create_window(200, 100);
+    s.map(parent);
+    assert!(s.wait_until(|s| s.focus() == parent));
+
+    let dialog = s.create_window(400, 300);
+    s.set_property(
+        dialog,
+        AtomEnum::WM_TRANSIENT_FOR.into(),
+        AtomEnum::WINDOW,
+        &[parent],
+    );
+    s.map(dialog);
+
+    assert!(s.wait_until(|s| {
+        let g = s.geometry(dialog);
+        (g.x, g.y) == (200, 150)
+    }));
+
+    let state = s.atoms(dialog, s.atom("_NET_WM_STATE"));
+    assert!(state.contains(&s.atom("_NET_WM_STATE_ABOVE")));
+
+    let stacking = s.stacking();
+    let pos = |w| stacking.iter().position(|&c| c == w).unwrap();
+    assert!(pos(dialog) > pos(parent));
+}
+
+#[test]
+fn windows_move_to_the_next_monitor() {
+    let Some(s) = Session::start(&[SCREEN, SCREEN]) else {
+        return;
+    };
+
+    let win = s.create_window(200, 100);
+    s.map(win);
+    assert!(s.wait_until(|s| s.focus() == win));
+    assert!(s.geometry(win).x < 800);
+
+    s.press(&[XK_Alt_L, XK_Shift_L], XK_period);
+
+    assert!(s.wait_until(|s| s.geometry(win).x >= 800));
+    assert_eq!(s.focus(), win);
+}
+
+#[test]
+fn virtual_monitors_split_a_single_screen() {
+    let monitors = [("KOAN_MONITORS", "400x600+0+0,400x600+400+0")];
+    let Some(s) = Session::start_with_env(&[SCREEN], &monitors) else {
+        return;
+    };
+
+    let win = s.create_window(200, 100);
+    s.map(win);
+    assert!(s.wait_until(|s| s.focus() == win));
+    assert!(s.geometry(win).width < 400);
+
+    s.press(&[XK_Alt_L, XK_Shift_L], XK_period);
+
+    assert!(s.wait_until(|s| s.geometry(win).x >= 400));
+}
+
+#[test]
+fn close_window_asks_the_client_to_delete_itself() {
+    let Some(s) = Session::start(&[SCREEN]) else {
+        return;
+    };
+
+    let wm_protocols = s.atom("WM_PROTOCOLS");
+    let wm_delete = s.atom("WM_DELETE_WINDOW");
+
+    let win = s.create_window(200, 100);
+    s.set_property(win, wm_protocols, AtomEnum::ATOM, &[wm_delete]);
+    s.map(win);
+    assert!(s.wait_until(|s| s.focus() == win));
+
+    s.press(&[XK_Alt_L, XK_Shift_L], XK_c);
+
+    let event = s.wait_for_event(|e| matches!(e, Event::ClientMessage(_)));
+    let Some(Event::ClientMessage(ev)) = event else {
+        panic!("no WM_DELETE_WINDOW message arrived");
+    };
+    assert_eq!(ev.window, win);
+    assert_eq!(ev.type_, wm_protocols);
+    assert_eq!(ev.data.as_data32()[0], wm_delete);
+}